@@ -1,21 +1,35 @@
 use std::{fmt, sync::Arc};
 
+use chrono::NaiveDate;
 use regex::Regex;
 
 use reqwest::{cookie::Jar, Url};
-use serde::Serialize;
-
-use crate::types::{
-    Eligibility, FundingInstrument, Identity, LoginResponse, PayRequestResponse, StoriesResponse,
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    cache::{Cache, Synced},
+    config, crash,
+    daemon::{Client, Older, PaymentParams, Prepared, Settings},
+    types::{
+        Eligibility, FundingInstrument, Identity, LoginResponse, PayRequestResponse, Person,
+        PersonEdge, StoriesResponse, Story,
+    },
 };
 
 pub struct Api {
     client: reqwest::Client,
     cookie_jar: Arc<Jar>,
     pub identity: Option<Identity>,
+    // venmo was unreachable at startup, so only cached data is available
+    pub offline: bool,
     csrf: String,
     bearer: String,
     db: sled::Db,
+    pub cache: Cache,
+    // a running daemon holds the db, so everything goes through it instead and `cache`
+    // is just a copy for this session
+    daemon: Option<Box<Client>>,
 }
 
 #[derive(Serialize)]
@@ -26,10 +40,22 @@ struct LoginQuery<'a> {
     is_group: bool,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum Audience {
     Private,
+    Friends,
+    Public,
+}
+
+impl fmt::Display for Audience {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Audience::Private => "private",
+            Audience::Friends => "friends",
+            Audience::Public => "public",
+        })
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -38,7 +64,7 @@ pub struct TargetUserDetails<'a> {
     user_id: &'a str,
 }
 
-#[derive(Serialize, PartialEq, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum PaymentType {
     Pay,
@@ -99,6 +125,8 @@ pub enum ApiError {
     LogoutFailure(String),
     PaymentSendFailure(String),
     UserQueryFailure(String),
+    Offline(String),
+    Daemon(String),
 }
 
 impl fmt::Display for ApiError {
@@ -113,10 +141,14 @@ impl fmt::Display for ApiError {
             ApiError::UserQueryFailure(e) => {
                 write!(f, "failed to query user! please retry... {e}")
             }
+            ApiError::Offline(e) => write!(f, "offline! {e}"),
+            ApiError::Daemon(e) => write!(f, "daemon request failed! {e}"),
         }
     }
 }
 
+impl std::error::Error for ApiError {}
+
 impl Api {
     fn set_cookies(&mut self, resp: &reqwest::Response) {
         resp.headers().iter().for_each(|v| {
@@ -143,10 +175,10 @@ impl Api {
         });
     }
 
-    async fn load_cookies(&mut self) {
+    fn load_cookies(&mut self) {
         let url = "https://venmo.com".parse::<Url>().unwrap();
 
-        self.db.iter().enumerate().for_each(|(_, v)| match v {
+        self.db.iter().for_each(|v| match v {
             Err(_) => {}
             Ok((v1, v2)) => {
                 let k = String::from_utf8(v1.to_vec()).expect("failed");
@@ -162,11 +194,14 @@ impl Api {
                 self.cookie_jar.add_cookie_str(&v, &url);
             }
         });
-
-        self.fetch_csrf().await.expect("failed to load csrf");
     }
 
     pub async fn logged_in(&mut self) -> bool {
+        crash::record_api_call("logged_in");
+        // the daemon wouldn't be running otherwise
+        if self.daemon.is_some() {
+            return true;
+        }
         match self.client.get("https://account.venmo.com/").send().await {
             Err(_) => false,
             Ok(resp) => resp.url().as_str() != "https://venmo.com/account/sign-in?next=%2F",
@@ -174,6 +209,7 @@ impl Api {
     }
 
     async fn fetch_csrf(&mut self) -> Result<(), ApiError> {
+        crash::record_api_call("fetch_csrf");
         // logged in
         let url = if self.logged_in().await {
             "https://account.venmo.com/"
@@ -189,16 +225,27 @@ impl Api {
             .send()
             .await
         {
+            // only a venmo we can't reach counts as offline, anything else is a real failure
+            Err(e) if e.is_connect() || e.is_timeout() => {
+                return Err(ApiError::Offline(e.to_string()));
+            }
             Err(e) => {
-                // todo: more verbose error for this case
                 return Err(ApiError::LoginFailure(e.to_string()));
             }
             Ok(v) => {
+                if !v.status().is_success() {
+                    return Err(ApiError::LoginFailure(format!(
+                        "venmo responded with {}",
+                        v.status()
+                    )));
+                }
                 self.set_cookies(&v);
                 let text = v.text().await.expect("failed to load text");
                 let csrf_regex =
                     Regex::new(r#""csrfToken":"([^"]*)""#).expect("failed to create regex");
-                let m = csrf_regex.find(&text).unwrap();
+                let m = csrf_regex.find(&text).ok_or_else(|| {
+                    ApiError::LoginFailure("couldn't find a csrf token".to_string())
+                })?;
                 text[m.start()..m.end()]
                     .split(":")
                     .map(|x| x.replace(r#"""#, ""))
@@ -215,30 +262,91 @@ impl Api {
 
         let client = reqwest::ClientBuilder::new()
             .cookie_provider(jar.clone())
-            .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:109.0) Gecko/20100101 Firefox/113.0")
+            .user_agent(&config::get().user_agent)
             .build()
             .expect("failed to build client");
 
+        let db = sled::open("db").expect("failed to initialize DB");
+
         let mut api = Api {
-            db: sled::open("db").expect("failed to initialize DB"),
+            cache: Cache::open(&db),
+            db,
             cookie_jar: jar,
             csrf: "".to_string(),
             bearer: "".to_string(),
             client,
             identity: None,
+            offline: false,
+            daemon: None,
         };
 
-        api.load_cookies().await;
-        api.fetch_csrf().await?;
+        api.load_cookies();
+
+        match api.fetch_csrf().await {
+            Ok(()) => {}
+            // fall back to the last-known identity if we've ever synced
+            Err(ApiError::Offline(e)) => match api.cache.identity() {
+                Some(identity) => {
+                    api.identity = Some(identity);
+                    api.offline = true;
+                }
+                None => return Err(ApiError::LoginFailure(e)),
+            },
+            Err(e) => return Err(e),
+        }
 
         Ok(api)
     }
 
+    // an api that goes through a running daemon
+    pub async fn remote(client: Client) -> Result<Self, ApiError> {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to initialize DB");
+
+        let mut api = Api {
+            cache: Cache::open(&db),
+            db,
+            cookie_jar: Arc::new(Jar::default()),
+            csrf: "".to_string(),
+            bearer: "".to_string(),
+            client: reqwest::Client::new(),
+            identity: None,
+            offline: false,
+            daemon: Some(Box::new(client)),
+        };
+
+        let settings: Settings = api.call("settings", json!({})).await?;
+        for person in settings.favourites.iter() {
+            api.cache.toggle_favourite(person);
+        }
+        api.cache
+            .set_default_funding_source(settings.default_source.as_deref());
+        api.get_profile().await?;
+
+        Ok(api)
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, ApiError> {
+        self.daemon
+            .as_mut()
+            .expect("not connected to a daemon")
+            .call(method, params)
+            .await
+            .map_err(|e| ApiError::Daemon(e.to_string()))
+    }
+
     pub async fn login(
         &mut self,
         username: &str,
         password: &str,
     ) -> Result<LoginResponse, ApiError> {
+        crash::record_api_call("login");
         let resp = match self
             .client
             .post("https://venmo.com/api/login")
@@ -269,6 +377,13 @@ impl Api {
     }
 
     pub async fn get_profile(&mut self) -> Result<Identity, ApiError> {
+        crash::record_api_call("get_profile");
+        if self.daemon.is_some() {
+            let identity: Identity = self.call("balance", json!({})).await?;
+            self.cache.set_identity(&identity);
+            self.identity = Some(identity.clone());
+            return Ok(identity);
+        }
         let profile_query = r#"
         query Identity($input: ProfileInput) {
           profile(input: $input) {
@@ -351,16 +466,19 @@ impl Api {
             .expect("failed to parse"),
         };
 
+        self.cache.set_identity(&id);
+        self.cache.touch();
         self.identity = Some(id.clone());
 
         Ok(id)
     }
 
     pub async fn get_recents(
-        &mut self,
+        &self,
         items_to_load: u32,
         prev: Option<&str>,
     ) -> Result<StoriesResponse, ApiError> {
+        crash::record_api_call("get_recents");
         if self.identity.is_none() {
             return Err(ApiError::Unauthaurized("Identity not found.".to_string()));
         }
@@ -380,13 +498,9 @@ impl Api {
                     ("externalId", &self.identity.as_ref().unwrap().id),
                     (
                         "nextId",
-                        if response.is_none() {
-                            match prev.as_ref() {
-                                Some(p) => p,
-                                None => "",
-                            }
-                        } else {
-                            &response.as_ref().unwrap().next_id
+                        match response.as_ref() {
+                            Some(r) => &r.next_id,
+                            None => prev.unwrap_or_default(),
                         },
                     ),
                 ])
@@ -416,21 +530,165 @@ impl Api {
     }
 
     pub async fn logout(&mut self) -> Result<(), ApiError> {
-        if let Err(_) = self
+        crash::record_api_call("logout");
+        if self.offline {
+            return Err(ApiError::Offline("can't logout right now.".to_string()));
+        }
+        if self.daemon.is_some() {
+            return Err(ApiError::LogoutFailure(
+                "the daemon is using this login, stop it first.".to_string(),
+            ));
+        }
+
+        if self
             .client
             .get("https://account.venmo.com/account/logout")
             .send()
             .await
+            .is_err()
         {
             return Err(ApiError::LogoutFailure(
                 "logout request failed! please try again".to_string(),
             ));
         }
         self.db.clear().expect("failed to clear db");
+        self.cache.clear();
         Ok(())
     }
 
+    // fetch stories newer than the cache, stopping at the first one we already have.
+    // returns the number of stories added
+    pub async fn sync_stories(&mut self, batch_size: u32) -> Result<usize, ApiError> {
+        Ok(self.sync_story_changes(batch_size).await?.added)
+    }
+
+    // the same, also returning the cached stories whose status changed
+    pub async fn sync_story_changes(&mut self, batch_size: u32) -> Result<Synced, ApiError> {
+        crash::record_api_call("sync_stories");
+        if self.offline {
+            return Err(ApiError::Offline("can't sync stories.".to_string()));
+        }
+        // the daemon keeps its own cache synced, so just pick up what it has
+        if self.daemon.is_some() {
+            return Ok(Synced {
+                added: self.copy_daemon_stories().await?,
+                changed: vec![],
+            });
+        }
+
+        let mut sync = self.cache.sync();
+        let mut next_id: Option<String> = None;
+        loop {
+            let resp = self.get_recents(batch_size, next_id.as_deref()).await?;
+            match sync.add_page(resp) {
+                Some(next) => next_id = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(sync.finish())
+    }
+
+    // page further back than the oldest cached story.
+    // returns the number of stories added
+    pub async fn load_older_stories(&mut self, batch_size: u32) -> Result<usize, ApiError> {
+        crash::record_api_call("load_older_stories");
+        if self.offline {
+            return Err(ApiError::Offline("can't load more stories.".to_string()));
+        }
+        if self.daemon.is_some() {
+            let older: Older = self.call("older", json!({})).await?;
+            if older.reached_end {
+                self.cache.set_cursor("");
+            }
+            return self.copy_daemon_stories().await;
+        }
+
+        if self.cache.reached_end() {
+            return Ok(0);
+        }
+
+        let cursor = self.cache.cursor();
+        let resp = self.get_recents(batch_size, cursor.as_deref()).await?;
+
+        let mut added = 0;
+        for story in resp.stories.iter() {
+            if !self.cache.contains(&story.id) {
+                self.cache.insert(story);
+                added += 1;
+            }
+        }
+        self.cache.set_cursor(&resp.next_id);
+
+        Ok(added)
+    }
+
+    // copy the daemon's cached stories into ours, returning how many were new
+    async fn copy_daemon_stories(&mut self) -> Result<usize, ApiError> {
+        let stories: Vec<Story> = self.call("stories", json!({})).await?;
+        let mut added = 0;
+        for story in stories.iter() {
+            if !self.cache.contains(&story.id) {
+                self.cache.insert(story);
+                added += 1;
+            }
+        }
+        self.cache.touch();
+        Ok(added)
+    }
+
+    // page back until the cache covers `day`, or there's nothing older
+    pub async fn load_stories_since(
+        &mut self,
+        day: NaiveDate,
+        batch_size: u32,
+    ) -> Result<(), ApiError> {
+        crash::record_api_call("load_stories_since");
+        loop {
+            let oldest = self.cache.stories().last().and_then(|s| s.day());
+            if oldest.is_some_and(|d| d < day) {
+                return Ok(());
+            }
+            if self.cache.reached_end() {
+                return Ok(());
+            }
+            self.load_older_stories(batch_size).await?;
+        }
+    }
+
+    // page back until the cache reaches the very first story
+    pub async fn load_all_stories(&mut self, batch_size: u32) -> Result<(), ApiError> {
+        crash::record_api_call("load_all_stories");
+        while !self.cache.reached_end() {
+            self.load_older_stories(batch_size).await?;
+        }
+        Ok(())
+    }
+
+    // the person whose handle is exactly `handle`, with or without the @
+    pub async fn find_person(&mut self, handle: &str) -> Result<Person, ApiError> {
+        let handle = handle.trim_start_matches('@');
+        self.search_people(handle)
+            .await?
+            .into_iter()
+            .find(|p| p.handle.eq_ignore_ascii_case(handle))
+            .ok_or_else(|| ApiError::UserQueryFailure(format!("no user with handle @{handle}.")))
+    }
+
     pub async fn fetch_user_id(&mut self, query: &str) -> Result<String, ApiError> {
+        match self.search_people(query).await?.into_iter().next() {
+            Some(person) => Ok(person.id),
+            None => Err(ApiError::UserQueryFailure(format!(
+                "no one found for '{query}'."
+            ))),
+        }
+    }
+
+    pub async fn search_people(&mut self, query: &str) -> Result<Vec<Person>, ApiError> {
+        crash::record_api_call("search_people");
+        if self.daemon.is_some() {
+            return self.call("search", json!({ "name": query })).await;
+        }
         let user_query = r#"
         query People(
             $input: SearchInput!
@@ -563,16 +821,15 @@ impl Api {
                     return Err(ApiError::UserQueryFailure(e.to_string()));
                 }
 
-                let id =
-                    parsed.unwrap()["data"]["search"]["people"]["edges"][0]["node"]["id"].clone();
+                let edges = parsed.unwrap()["data"]["search"]["people"]["edges"].clone();
 
-                let id_as_str = serde_json::from_value::<String>(id);
+                let edges_parsed = serde_json::from_value::<Vec<PersonEdge>>(edges);
 
-                if let Err(e) = id_as_str {
+                if let Err(e) = edges_parsed {
                     return Err(ApiError::UserQueryFailure(e.to_string()));
                 }
 
-                id_as_str.unwrap()
+                edges_parsed.unwrap().into_iter().map(|e| e.node).collect()
             }
         };
 
@@ -583,13 +840,32 @@ impl Api {
         &mut self,
         amount_in_cents: u32,
         note: &'a str,
-        user_id: &'a str,
+        recipient: &'a Person,
         payment_type: PaymentType,
         funding_source_id: Option<&'a str>,
+        token: Option<&'a str>,
     ) -> Result<PayRequestResponse, ApiError> {
+        crash::record_api_call("submit_payment");
+        if self.offline {
+            return Err(ApiError::Offline("payments are disabled.".to_string()));
+        }
+        // the daemon only sends what was prepared with it, see prepare_payment
+        if self.daemon.is_some() {
+            let Some(token) = token else {
+                return Err(ApiError::PaymentSendFailure(
+                    "the payment wasn't prepared with the daemon.".to_string(),
+                ));
+            };
+            let sent = self.call("confirm", json!({ "token": token })).await?;
+            if payment_type == PaymentType::Pay {
+                self.cache.clear_funding_instruments();
+            }
+            return Ok(sent);
+        }
+
         let eligibility_token = if payment_type == PaymentType::Pay {
             let eligibility = self
-                .fetch_eligibility(amount_in_cents, note, user_id)
+                .fetch_eligibility(amount_in_cents, note, &recipient.id)
                 .await?;
             if eligibility.eligible && eligibility.eligibility_token.is_some() {
                 eligibility.eligibility_token
@@ -608,9 +884,11 @@ impl Api {
             .header("xsrf-token", &self.csrf)
             .json(&PaymentQuery {
                 amount_in_cents,
-                audience: Audience::Private,
+                audience: config::get().audience,
                 note,
-                target_user_details: TargetUserDetails { user_id },
+                target_user_details: TargetUserDetails {
+                    user_id: &recipient.id,
+                },
                 payment_type,
                 eligibility_token,
                 funding_source_id,
@@ -627,16 +905,57 @@ impl Api {
                 return Err(ApiError::PaymentSendFailure(e.to_string()));
             }
         };
+        if payment_type == PaymentType::Pay {
+            self.cache.clear_funding_instruments();
+        }
 
         Ok(parsed)
     }
 
+    // hand a reviewed payment to the daemon, getting back the token submit_payment confirms
+    // it with. without a daemon there's nothing to prepare
+    pub async fn prepare_payment<'a>(
+        &mut self,
+        amount_in_cents: u32,
+        note: &'a str,
+        recipient: &'a Person,
+        payment_type: PaymentType,
+        funding_source_id: Option<&'a str>,
+    ) -> Result<Option<String>, ApiError> {
+        crash::record_api_call("prepare_payment");
+        if self.daemon.is_none() {
+            return Ok(None);
+        }
+        let method = match payment_type {
+            PaymentType::Pay => "pay",
+            PaymentType::Request => "request",
+        };
+        let payment = PaymentParams {
+            handle: recipient.handle.clone(),
+            user_id: Some(recipient.id.clone()),
+            amount_cents: amount_in_cents,
+            note: note.to_string(),
+            source: funding_source_id.map(str::to_string),
+        };
+        let prepared: Prepared = self.call(method, json!(payment)).await?;
+        Ok(Some(prepared.token))
+    }
+
     pub async fn fetch_eligibility<'a>(
         &mut self,
         amount_in_cents: u32,
         note: &'a str,
         user_id: &'a str,
     ) -> Result<Eligibility, ApiError> {
+        crash::record_api_call("fetch_eligibility");
+        if self.daemon.is_some() {
+            return self
+                .call(
+                    "eligibility",
+                    json!({ "amount_cents": amount_in_cents, "note": note, "user_id": user_id }),
+                )
+                .await;
+        }
         let eligibility = match self
             .client
             .post("https://account.venmo.com/api/eligibility")
@@ -669,6 +988,16 @@ impl Api {
     }
 
     pub async fn get_funding_instruments(&mut self) -> Result<Vec<FundingInstrument>, ApiError> {
+        crash::record_api_call("get_funding_instruments");
+        if self.offline {
+            return Ok(self.cache.funding_instruments().unwrap_or_default());
+        }
+        if self.daemon.is_some() {
+            let instruments: Vec<FundingInstrument> = self.call("instruments", json!({})).await?;
+            self.cache.set_funding_instruments(&instruments);
+            return Ok(instruments);
+        }
+
         let q = r#"
         query getUserFundingInstruments {
             profile {
@@ -767,7 +1096,7 @@ impl Api {
                 let instruments = parsed["data"]["profile"]["wallet"].as_array().unwrap();
 
                 let parsed_instruments = instruments
-                    .into_iter()
+                    .iter()
                     .filter_map(|i| {
                         let i_parsed = serde_json::from_value::<FundingInstrument>(i.clone());
                         if i_parsed.is_err() {
@@ -781,6 +1110,33 @@ impl Api {
             }
         };
 
+        self.cache.set_funding_instruments(&id);
+
         Ok(id)
     }
+
+    // the source payments start on: the one picked on the wallet page, then the config's
+    pub fn default_funding_source(&self) -> Option<String> {
+        self.cache
+            .default_funding_source()
+            .or_else(|| config::get().default_funding_source.clone())
+    }
+
+    pub async fn set_default_funding_source(&mut self, id: Option<&str>) -> Result<(), ApiError> {
+        if self.daemon.is_some() {
+            self.call::<bool>("set_default_source", json!({ "id": id }))
+                .await?;
+        }
+        self.cache.set_default_funding_source(id);
+        Ok(())
+    }
+
+    // pin someone, or unpin them if they already are. true if they're pinned now
+    pub async fn toggle_favourite(&mut self, person: &Person) -> Result<bool, ApiError> {
+        if self.daemon.is_some() {
+            self.call::<bool>("toggle_favourite", json!({ "person": person }))
+                .await?;
+        }
+        Ok(self.cache.toggle_favourite(person))
+    }
 }
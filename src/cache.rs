@@ -0,0 +1,384 @@
+use chrono::{DateTime, Local};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::types::{FundingInstrument, Identity, Person, StoriesResponse, Story};
+
+const CURSOR_KEY: &str = "next_id";
+const DEFAULT_SOURCE_KEY: &str = "default_funding_source";
+const END_KEY: &str = "reached_end";
+const FAVOURITES_KEY: &str = "favourites";
+const IDENTITY_KEY: &str = "identity";
+const INSTRUMENTS_KEY: &str = "funding_instruments";
+const SYNCED_AT_KEY: &str = "synced_at";
+
+// last-known account data persisted across sessions. stories are keyed by story id,
+// everything else lives in `meta`
+pub struct Cache {
+    stories: sled::Tree,
+    meta: sled::Tree,
+}
+
+impl Cache {
+    pub fn open(db: &sled::Db) -> Self {
+        Self {
+            stories: db
+                .open_tree("stories")
+                .expect("failed to open stories tree"),
+            meta: db.open_tree("meta").expect("failed to open meta tree"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stories.is_empty()
+    }
+
+    pub fn story(&self, id: &str) -> Option<Story> {
+        self.stories
+            .get(id)
+            .expect("failed to read from db")
+            .and_then(|v| serde_json::from_slice(&v).ok())
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.stories
+            .contains_key(id)
+            .expect("failed to read from db")
+    }
+
+    pub fn insert(&self, story: &Story) {
+        self.stories
+            .insert(
+                &story.id,
+                serde_json::to_vec(story).expect("failed to serialize story"),
+            )
+            .expect("failed to add value to db");
+    }
+
+    // all cached stories, newest first
+    pub fn stories(&self) -> Vec<Story> {
+        let mut stories = self
+            .stories
+            .iter()
+            .filter_map(|v| match v {
+                Err(_) => None,
+                Ok((_, v)) => serde_json::from_slice::<Story>(&v).ok(),
+            })
+            .collect::<Vec<_>>();
+
+        stories.sort_by(|a, b| b.date.cmp(&a.date));
+        stories
+    }
+
+    // where to continue paging from when loading stories older than the cache
+    pub fn cursor(&self) -> Option<String> {
+        self.meta
+            .get(CURSOR_KEY)
+            .expect("failed to read from db")
+            .map(|v| String::from_utf8(v.to_vec()).expect("failed"))
+    }
+
+    // an empty next id means there's nothing older left to page through
+    pub fn set_cursor(&self, next_id: &str) {
+        if next_id.is_empty() {
+            self.set(END_KEY, &true);
+            return;
+        }
+        self.meta
+            .insert(CURSOR_KEY, next_id)
+            .expect("failed to add value to db");
+    }
+
+    // whether the cache goes all the way back to the first story
+    pub fn reached_end(&self) -> bool {
+        self.get(END_KEY).unwrap_or(false)
+    }
+
+    fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.meta
+            .get(key)
+            .expect("failed to read from db")
+            .and_then(|v| serde_json::from_slice::<T>(&v).ok())
+    }
+
+    fn set<T: Serialize + ?Sized>(&self, key: &str, value: &T) {
+        self.meta
+            .insert(
+                key,
+                serde_json::to_vec(value).expect("failed to serialize value"),
+            )
+            .expect("failed to add value to db");
+    }
+
+    pub fn identity(&self) -> Option<Identity> {
+        self.get(IDENTITY_KEY)
+    }
+
+    pub fn set_identity(&self, identity: &Identity) {
+        self.set(IDENTITY_KEY, identity);
+    }
+
+    pub fn funding_instruments(&self) -> Option<Vec<FundingInstrument>> {
+        self.get(INSTRUMENTS_KEY)
+    }
+
+    pub fn set_funding_instruments(&self, instruments: &[FundingInstrument]) {
+        self.set(INSTRUMENTS_KEY, instruments);
+    }
+
+    // balances are stale once a payment goes out, so the next look goes to venmo
+    pub fn clear_funding_instruments(&self) {
+        self.meta
+            .remove(INSTRUMENTS_KEY)
+            .expect("failed to remove value from db");
+    }
+
+    // the funding source picked on the wallet page, if any
+    pub fn default_funding_source(&self) -> Option<String> {
+        self.get(DEFAULT_SOURCE_KEY)
+    }
+
+    pub fn set_default_funding_source(&self, id: Option<&str>) {
+        match id {
+            Some(id) => self.set(DEFAULT_SOURCE_KEY, id),
+            None => {
+                self.meta
+                    .remove(DEFAULT_SOURCE_KEY)
+                    .expect("failed to remove value from db");
+            }
+        }
+    }
+
+    // recipients pinned from the pay page, oldest first
+    pub fn favourites(&self) -> Vec<Person> {
+        self.get(FAVOURITES_KEY).unwrap_or_default()
+    }
+
+    // pin someone, or unpin them if they already are. true if they're pinned now
+    pub fn toggle_favourite(&self, person: &Person) -> bool {
+        let mut favourites = self.favourites();
+        let pinned = match favourites.iter().position(|p| p.id == person.id) {
+            Some(i) => {
+                favourites.remove(i);
+                false
+            }
+            None => {
+                favourites.push(person.clone());
+                true
+            }
+        };
+        self.set(FAVOURITES_KEY, &favourites);
+        pinned
+    }
+
+    // when data was last fetched from venmo
+    pub fn synced_at(&self) -> Option<DateTime<Local>> {
+        self.get(SYNCED_AT_KEY)
+    }
+
+    pub fn touch(&self) {
+        self.set(SYNCED_AT_KEY, &Local::now());
+    }
+
+    pub fn clear(&self) {
+        self.stories.clear().expect("failed to clear db");
+        self.meta.clear().expect("failed to clear db");
+    }
+
+    // start bringing the cache up to date, see StorySync
+    pub fn sync(&self) -> StorySync<'_> {
+        StorySync {
+            was_empty: self.is_empty(),
+            cache: self,
+            fresh: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+// a sync of stories newer than the cache, fed the newest pages first. it stops at the first
+// story we already have, and only caches the new ones once they join up with the old, since a
+// sync that failed partway would otherwise leave a gap the next sync stops short of
+pub struct StorySync<'a> {
+    cache: &'a Cache,
+    was_empty: bool,
+    fresh: Vec<Story>,
+    changed: Vec<Story>,
+}
+
+// what a sync found
+#[derive(Debug, Default)]
+pub struct Synced {
+    pub added: usize,
+    // stories we already had whose status moved on, e.g. a pending payment that settled
+    pub changed: Vec<Story>,
+}
+
+impl<'a> StorySync<'a> {
+    // the id of the next page to fetch, or None once the sync is done
+    pub fn add_page(&mut self, page: StoriesResponse) -> Option<String> {
+        let mut reached_known = false;
+        for story in page.stories.iter() {
+            match self.cache.story(&story.id) {
+                // the rest of the page is known too, but its statuses may have changed
+                Some(cached) => {
+                    reached_known = true;
+                    if cached.status != story.status {
+                        self.changed.push(story.clone());
+                    }
+                }
+                None if !reached_known => self.fresh.push(story.clone()),
+                None => {}
+            }
+        }
+
+        // nothing cached yet, so older pages are left for `load_older_stories`
+        if self.was_empty {
+            self.cache.set_cursor(&page.next_id);
+            return None;
+        }
+
+        if reached_known || page.stories.is_empty() || page.next_id.is_empty() {
+            return None;
+        }
+        Some(page.next_id)
+    }
+
+    // cache what was found
+    pub fn finish(self) -> Synced {
+        for story in self.fresh.iter().chain(self.changed.iter()) {
+            self.cache.insert(story);
+        }
+        self.cache.touch();
+        Synced {
+            added: self.fresh.len(),
+            changed: self.changed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> Cache {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to initialize DB");
+        Cache::open(&db)
+    }
+
+    fn story(n: u32) -> Story {
+        Story::fixture(
+            &n.to_string(),
+            &format!("2026-01-{n:02}T12:00:00"),
+            "- $1.00",
+            "bob",
+            "",
+        )
+    }
+
+    // stories `from` down to `to`, newest first like venmo sends them
+    fn page(from: u32, to: u32, next_id: &str) -> StoriesResponse {
+        StoriesResponse {
+            next_id: next_id.to_string(),
+            stories: (to..=from).rev().map(story).collect(),
+        }
+    }
+
+    fn ids(cache: &Cache) -> Vec<String> {
+        cache.stories().into_iter().map(|s| s.id).collect()
+    }
+
+    #[test]
+    fn up_to_date_sync_adds_nothing() {
+        let cache = cache();
+        cache.insert(&story(1));
+        cache.insert(&story(2));
+
+        let mut sync = cache.sync();
+        assert_eq!(sync.add_page(page(2, 1, "next")), None);
+        assert_eq!(sync.finish().added, 0);
+        assert_eq!(ids(&cache), ["2", "1"]);
+    }
+
+    #[test]
+    fn sync_fills_the_gap_before_caching_anything() {
+        let cache = cache();
+        cache.insert(&story(1));
+
+        let mut sync = cache.sync();
+        assert_eq!(sync.add_page(page(5, 4, "p2")), Some("p2".to_string()));
+        // nothing written until the pages reach a story we had
+        assert_eq!(ids(&cache), ["1"]);
+        assert_eq!(sync.add_page(page(3, 1, "p3")), None);
+        assert_eq!(sync.finish().added, 4);
+        assert_eq!(ids(&cache), ["5", "4", "3", "2", "1"]);
+    }
+
+    #[test]
+    fn sync_picks_up_status_changes() {
+        let cache = cache();
+        let mut pending = story(2);
+        pending.status = Some("pending".to_string());
+        cache.insert(&story(1));
+        cache.insert(&pending);
+
+        let mut settled = page(3, 1, "next");
+        settled.stories[1].status = Some("settled".to_string());
+        let mut sync = cache.sync();
+        assert_eq!(sync.add_page(settled), None);
+        let synced = sync.finish();
+        assert_eq!(synced.added, 1);
+        let changed = synced
+            .changed
+            .iter()
+            .map(|s| s.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(changed, ["2"]);
+        assert_eq!(cache.story("2").unwrap().status.as_deref(), Some("settled"));
+    }
+
+    #[test]
+    fn failed_sync_leaves_the_cache_alone() {
+        let cache = cache();
+        cache.insert(&story(1));
+
+        let mut sync = cache.sync();
+        sync.add_page(page(5, 4, "p2"));
+        // the next page never came
+        drop(sync);
+        assert_eq!(ids(&cache), ["1"]);
+    }
+
+    #[test]
+    fn first_sync_keeps_one_page_and_the_cursor() {
+        let cache = cache();
+        let mut sync = cache.sync();
+        assert_eq!(sync.add_page(page(5, 4, "p2")), None);
+        assert_eq!(sync.finish().added, 2);
+        assert_eq!(cache.cursor().as_deref(), Some("p2"));
+        assert!(!cache.reached_end());
+    }
+
+    #[test]
+    fn empty_cursor_marks_the_end_of_history() {
+        let cache = cache();
+        let mut sync = cache.sync();
+        assert_eq!(sync.add_page(page(2, 1, "")), None);
+        sync.finish();
+        assert!(cache.reached_end());
+        // not a cursor, which would page from the newest stories again
+        assert_eq!(cache.cursor(), None);
+    }
+
+    #[test]
+    fn clearing_instruments_leaves_nothing_cached() {
+        let cache = cache();
+        cache.set_funding_instruments(&[]);
+        assert_eq!(cache.funding_instruments().map(|i| i.len()), Some(0));
+
+        cache.clear_funding_instruments();
+        assert!(cache.funding_instruments().is_none());
+    }
+}
@@ -0,0 +1,612 @@
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    api::{Api, ApiError, PaymentType},
+    config,
+    daemon::{socket_path, Client, PaymentParams, Prepared, RpcError, API_ERROR},
+    export::{export, Format, Record},
+    journal::{synced_ids, Categorizer, JournalFormat, JournalRules},
+    search::Query,
+    types::{
+        format_cents, format_dollars, FundingInstrument, Identity, PayRequestResponse, Person,
+        Story, StorySubType,
+    },
+};
+
+const MAX_BACKOFF: Duration = Duration::from_secs(600);
+
+// where commands get their data: a running daemon if there is one, otherwise
+// the login stored by the tui (sled only lets one process open it at a time)
+pub enum Session {
+    Local(Api),
+    Daemon(Client),
+}
+
+// the running daemon, if there is one. a socket we can't trust is an error rather
+// than a reason to fall back to the db
+pub async fn connect_daemon() -> io::Result<Option<Client>> {
+    match Client::connect(&socket_path()).await {
+        Ok(client) => Ok(Some(client)),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Err(e),
+        Err(_) => Ok(None),
+    }
+}
+
+pub async fn session() -> Result<Session, Box<dyn Error>> {
+    if let Some(client) = connect_daemon().await? {
+        return Ok(Session::Daemon(client));
+    }
+
+    let mut api = Api::new().await?;
+
+    if !api.offline {
+        if !api.logged_in().await {
+            return Err("not logged in! run venmo-tui without a command to login".into());
+        }
+        api.get_profile().await?;
+    }
+
+    Ok(Session::Local(api))
+}
+
+// the tui's api, going through the daemon when one is running since it holds the db
+pub async fn tui_api() -> Result<Api, Box<dyn Error>> {
+    match connect_daemon().await? {
+        Some(client) => Ok(Api::remote(client).await?),
+        None => Ok(Api::new().await?),
+    }
+}
+
+impl Session {
+    async fn identity(&mut self) -> Result<Identity, Box<dyn Error>> {
+        match self {
+            Session::Local(api) => Ok(api.identity.clone().unwrap()),
+            Session::Daemon(client) => client.call("balance", json!({})).await,
+        }
+    }
+
+    // stories within the range, newest first, after syncing (and paging back
+    // far enough to cover `after`) when online
+    async fn history(
+        &mut self,
+        after: Option<NaiveDate>,
+        before: Option<NaiveDate>,
+    ) -> Result<Vec<Story>, Box<dyn Error>> {
+        let api = match self {
+            Session::Local(api) => api,
+            Session::Daemon(client) => {
+                return client
+                    .call("stories", json!({ "since": after, "until": before }))
+                    .await
+            }
+        };
+
+        if !api.offline {
+            api.sync_stories(config::get().page_size).await?;
+            if let Some(after) = after {
+                api.load_stories_since(after, config::get().page_size)
+                    .await?;
+            }
+        }
+
+        let query = Query {
+            after,
+            before,
+            ..Query::default()
+        };
+
+        Ok(api
+            .cache
+            .stories()
+            .into_iter()
+            .filter(|s| query.matches(s))
+            .collect())
+    }
+
+    // every story back to the first one, after syncing when online
+    async fn full_history(&mut self) -> Result<Vec<Story>, Box<dyn Error>> {
+        let api = match self {
+            Session::Local(api) => api,
+            Session::Daemon(client) => return client.call("stories", json!({ "all": true })).await,
+        };
+
+        if !api.offline {
+            api.sync_stories(config::get().page_size).await?;
+            api.load_all_stories(config::get().page_size).await?;
+        }
+
+        Ok(api.cache.stories())
+    }
+
+    // when the cached data was last synced, if it's all there is because we're offline
+    fn offline_since(&self) -> Option<Option<DateTime<Local>>> {
+        match self {
+            Session::Local(api) if api.offline => Some(api.cache.synced_at()),
+            _ => None,
+        }
+    }
+
+    // ask the daemon to announce new stories, for `poll`
+    async fn subscribe(&mut self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Session::Local(_) => Ok(()),
+            Session::Daemon(client) => client
+                .call::<bool>("subscribe", json!({}))
+                .await
+                .map(|_| ()),
+        }
+    }
+
+    // connect to the daemon again after losing it, e.g. when it restarts
+    async fn reconnect(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Session::Daemon(client) = self {
+            *client = Client::connect(&socket_path()).await?;
+        }
+        self.subscribe().await
+    }
+
+    // stories that are new since the last sync, oldest first. the daemon polls by
+    // itself, so this waits for its next announcement
+    async fn poll(&mut self) -> Result<Vec<Story>, Box<dyn Error>> {
+        match self {
+            Session::Local(api) => {
+                let added = api.sync_stories(config::get().page_size).await?;
+                Ok(api.cache.stories().into_iter().take(added).rev().collect())
+            }
+            Session::Daemon(client) => Ok(vec![client.next_story().await?]),
+        }
+    }
+
+    async fn search_people(&mut self, name: &str) -> Result<Vec<Person>, Box<dyn Error>> {
+        match self {
+            Session::Local(api) => Ok(api.search_people(name).await?),
+            Session::Daemon(client) => client.call("search", json!({ "name": name })).await,
+        }
+    }
+
+    async fn instruments(&mut self) -> Result<Vec<FundingInstrument>, Box<dyn Error>> {
+        match self {
+            Session::Local(api) => Ok(api.get_funding_instruments().await?),
+            Session::Daemon(client) => client.call("instruments", json!({})).await,
+        }
+    }
+
+    // resolves the recipient, then sends only once `yes` or the user at the prompt
+    // has seen who it's going to
+    async fn send_payment(
+        &mut self,
+        payment_type: PaymentType,
+        payment: PaymentParams,
+        yes: bool,
+    ) -> Result<(Person, PayRequestResponse), Box<dyn Error>> {
+        match self {
+            Session::Local(api) => {
+                let person = api.find_person(&payment.handle).await?;
+                confirm(payment_type, &payment, &person, yes)?;
+                // the same default the tui pays with
+                let source = match payment_type {
+                    PaymentType::Pay => payment
+                        .source
+                        .clone()
+                        .or_else(|| api.default_funding_source()),
+                    PaymentType::Request => None,
+                };
+                let resp = api
+                    .submit_payment(
+                        payment.amount_cents,
+                        &payment.note,
+                        &person,
+                        payment_type,
+                        source.as_deref(),
+                        None,
+                    )
+                    .await?;
+                Ok((person, resp))
+            }
+            Session::Daemon(client) => {
+                let method = match payment_type {
+                    PaymentType::Pay => "pay",
+                    PaymentType::Request => "request",
+                };
+                let prepared: Prepared = client.call(method, json!(payment)).await?;
+                confirm(payment_type, &payment, &prepared.recipient, yes)?;
+                let resp = client
+                    .call("confirm", json!({ "token": prepared.token }))
+                    .await?;
+                Ok((prepared.recipient, resp))
+            }
+        }
+    }
+}
+
+pub async fn export_history(
+    session: &mut Session,
+    format: Format,
+    after: Option<NaiveDate>,
+    before: Option<NaiveDate>,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let stories = session.history(after, before).await?;
+
+    let identity = session.identity().await?;
+    match output {
+        Some(path) => export(&mut File::create(path)?, format, &stories, &identity)?,
+        None => export(&mut io::stdout().lock(), format, &stories, &identity)?,
+    }
+
+    Ok(())
+}
+
+// append stories that aren't in the journal yet, oldest first
+pub async fn sync_journal(
+    session: &mut Session,
+    path: PathBuf,
+    format: JournalFormat,
+    rules: Option<PathBuf>,
+    after: Option<NaiveDate>,
+) -> Result<(), Box<dyn Error>> {
+    let categorizer = match rules {
+        Some(rules) => Categorizer::from_toml(&fs::read_to_string(rules)?)?,
+        None => Categorizer::new(JournalRules::default())?,
+    };
+
+    let synced = match fs::read_to_string(&path) {
+        Ok(text) => synced_ids(&text),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Default::default(),
+        Err(e) => return Err(e.into()),
+    };
+
+    // a fresh journal with no start day gets everything, not just the latest page
+    let stories = if synced.is_empty() && after.is_none() {
+        session.full_history().await?
+    } else {
+        session.history(after, None).await?
+    };
+
+    let stories = stories
+        .into_iter()
+        .rev()
+        .filter(|s| !synced.contains(&s.id))
+        .collect::<Vec<_>>();
+
+    let mut journal = OpenOptions::new().create(true).append(true).open(&path)?;
+    for story in stories.iter() {
+        categorizer.write_entry(&mut journal, format, story)?;
+    }
+    journal.flush()?;
+
+    eprintln!("added {} transactions to {}", stories.len(), path.display());
+
+    Ok(())
+}
+
+// api failures exit with 3 so scripts can tell them apart from other errors (1)
+// and bad arguments (2, from clap)
+pub fn exit_code(e: &(dyn Error + 'static)) -> i32 {
+    let from_daemon = e
+        .downcast_ref::<RpcError>()
+        .is_some_and(|e| e.code == API_ERROR);
+    if from_daemon || e.downcast_ref::<ApiError>().is_some() {
+        3
+    } else {
+        1
+    }
+}
+
+// dollars like "12.50" or "$12" into cents. no rounding, so anything finer than a cent is refused
+pub fn parse_amount(s: &str) -> Result<u32, String> {
+    let digits = s.trim_start_matches('$');
+    let (dollars, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+    let numeric = |v: &str| v.chars().all(|c| c.is_ascii_digit());
+    if !numeric(dollars) || !numeric(fraction) || (dollars.is_empty() && fraction.is_empty()) {
+        return Err(format!("'{s}' isn't an amount"));
+    }
+    if fraction.len() > 2 {
+        return Err(format!("'{s}' has more than 2 decimal places"));
+    }
+
+    let dollars = if dollars.is_empty() { "0" } else { dollars };
+    let cents = dollars
+        .parse::<u32>()
+        .ok()
+        .and_then(|d| d.checked_mul(100))
+        .and_then(|c| c.checked_add(format!("{fraction:0<2}").parse::<u32>().ok()?))
+        .ok_or_else(|| format!("'{s}' is too large"))?;
+
+    if cents == 0 {
+        return Err(format!("'{s}' isn't a positive amount"));
+    }
+    Ok(cents)
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<(), Box<dyn Error>> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+pub async fn balance(session: &mut Session, json: bool) -> Result<(), Box<dyn Error>> {
+    let identity = session.identity().await?;
+    let cents = (identity.balance.user_balance.value as f64 * 100.0).round() as i64;
+    let stale = session.offline_since();
+
+    if json {
+        print_json(&serde_json::json!({
+            "handle": identity.handle,
+            "balance": format_cents(cents),
+            "balance_cents": cents,
+            "stale": stale.is_some(),
+            "synced_at": stale.flatten(),
+        }))
+    } else {
+        println!("{}", format_cents(cents));
+        // on stderr so scripts reading the amount aren't thrown off
+        if let Some(synced_at) = stale {
+            eprintln!(
+                "offline: cached balance from {}",
+                synced_at
+                    .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "an unknown time".to_string())
+            );
+        }
+        Ok(())
+    }
+}
+
+// one story per line, tab separated or as json lines
+pub async fn print_history(
+    session: &mut Session,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    for story in session.history(since, until).await? {
+        let r = Record::new(&story);
+        if json {
+            print_json(&r)?;
+        } else {
+            println!(
+                "{}\t{}\t{}\t{}\t{}",
+                r.date.map(|d| d.to_string()).unwrap_or_default(),
+                r.id,
+                r.amount,
+                r.counterparty.unwrap_or_default(),
+                r.note.replace(['\t', '\n'], " ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn print_people(people: &[Person], json: bool) -> Result<(), Box<dyn Error>> {
+    for person in people {
+        if json {
+            print_json(person)?;
+        } else {
+            println!("@{}\t{}\t{}", person.handle, person.display_name, person.id);
+        }
+    }
+    Ok(())
+}
+
+pub async fn search_people(
+    session: &mut Session,
+    name: &str,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let people = session.search_people(name).await?;
+    print_people(&people, json)
+}
+
+// ask on the terminal before money moves, unless --yes already said so
+fn confirm(
+    payment_type: PaymentType,
+    payment: &PaymentParams,
+    person: &Person,
+    yes: bool,
+) -> Result<(), Box<dyn Error>> {
+    if yes {
+        return Ok(());
+    }
+    if !io::stdin().is_terminal() {
+        return Err("not a terminal, pass --yes to send without confirming".into());
+    }
+
+    eprint!(
+        "{} {} {} {} (@{}){}? [y/N] ",
+        match payment_type {
+            PaymentType::Pay => "pay",
+            PaymentType::Request => "request",
+        },
+        format_dollars(payment.amount_cents as i64),
+        match payment_type {
+            PaymentType::Pay => "to",
+            PaymentType::Request => "from",
+        },
+        person.display_name,
+        person.handle,
+        if payment.note.is_empty() {
+            String::new()
+        } else {
+            format!(" for \"{}\"", payment.note)
+        }
+    );
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err("cancelled, nothing was sent".into()),
+    }
+}
+
+pub async fn send_payment(
+    session: &mut Session,
+    payment_type: PaymentType,
+    payment: PaymentParams,
+    yes: bool,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let amount_cents = payment.amount_cents;
+    let (person, resp) = session
+        .send_payment(payment_type, payment.clone(), yes)
+        .await?;
+    let note = payment.note;
+
+    if json {
+        print_json(&serde_json::json!({
+            "type": payment_type,
+            "handle": person.handle,
+            "amount": format_cents(amount_cents as i64),
+            "note": note,
+            "status": resp.status,
+        }))
+    } else {
+        let status = serde_json::to_value(&resp.status)?;
+        println!(
+            "{} {} {} @{}: {}",
+            match payment_type {
+                PaymentType::Pay => "paid",
+                PaymentType::Request => "requested",
+            },
+            format_dollars(amount_cents as i64),
+            match payment_type {
+                PaymentType::Pay => "to",
+                PaymentType::Request => "from",
+            },
+            person.handle,
+            status.as_str().unwrap_or_default()
+        );
+        Ok(())
+    }
+}
+
+pub async fn instruments(session: &mut Session, json: bool) -> Result<(), Box<dyn Error>> {
+    for instrument in session.instruments().await? {
+        if json {
+            print_json(&instrument)?;
+        } else {
+            println!(
+                "{}\t{}\t{}",
+                instrument.id, instrument.instrument_type, instrument.name
+            );
+        }
+    }
+    Ok(())
+}
+
+// print new stories as json lines until interrupted, backing off while venmo is unreachable
+pub async fn tail(
+    session: &mut Session,
+    interval: Duration,
+    since: Option<NaiveDate>,
+    types: Vec<StorySubType>,
+) -> Result<(), Box<dyn Error>> {
+    if let Session::Local(api) = session {
+        if api.offline {
+            return Err(ApiError::Offline("can't tail stories.".to_string()).into());
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let emit = |seen: &mut HashSet<String>, story: &Story| {
+        let wanted = types.is_empty() || types.contains(&story.title.payload.sub_type);
+        if seen.insert(story.id.clone()) && wanted {
+            print_json(&Record::new(story))
+        } else {
+            Ok(())
+        }
+    };
+
+    session.subscribe().await?;
+    if let Some(since) = since {
+        for story in session.history(Some(since), None).await?.iter().rev() {
+            emit(&mut seen, story)?;
+        }
+    }
+    // whatever was already there when we started isn't new
+    for story in session.history(None, None).await? {
+        seen.insert(story.id);
+    }
+
+    let mut delay = interval;
+    loop {
+        let local = matches!(session, Session::Local(_));
+        if local {
+            tokio::time::sleep(delay).await;
+        }
+
+        match session.poll().await {
+            Ok(stories) => {
+                delay = interval;
+                for story in stories.iter() {
+                    emit(&mut seen, story)?;
+                }
+            }
+            Err(e) => {
+                delay = (delay * 2).min(MAX_BACKOFF);
+                eprintln!("{e} (retrying in {}s)", delay.as_secs());
+                if local {
+                    continue;
+                }
+
+                // the daemon went away, so wait for it to come back and catch up on
+                // anything it announced in the meantime
+                tokio::time::sleep(delay).await;
+                if let Err(e) = session.reconnect().await {
+                    eprintln!("{e}");
+                    continue;
+                }
+                delay = interval;
+                match session.history(None, None).await {
+                    Ok(stories) => {
+                        for story in stories.iter().rev() {
+                            emit(&mut seen, story)?;
+                        }
+                    }
+                    Err(e) => eprintln!("{e}"),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_amounts() {
+        assert_eq!(parse_amount("12.50"), Ok(1250));
+        assert_eq!(parse_amount("$12"), Ok(1200));
+        assert_eq!(parse_amount("0.5"), Ok(50));
+        assert_eq!(parse_amount(".05"), Ok(5));
+        assert_eq!(parse_amount("0.01"), Ok(1));
+    }
+
+    #[test]
+    fn refuses_amounts_that_arent_whole_cents() {
+        assert!(parse_amount("0").is_err());
+        assert!(parse_amount("0.00").is_err());
+        assert!(parse_amount("0.001").is_err());
+        assert!(parse_amount("1.005").is_err());
+        assert!(parse_amount("-5").is_err());
+        assert!(parse_amount("1e3").is_err());
+        assert!(parse_amount(".").is_err());
+        assert!(parse_amount("").is_err());
+        assert!(parse_amount("99999999999").is_err());
+    }
+}
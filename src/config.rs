@@ -0,0 +1,181 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use chrono::format::{Item, StrftimeItems};
+use serde::Deserialize;
+
+use crate::{
+    api::Audience,
+    hooks::Hooks,
+    keymap::Keymap,
+    theme::{Theme, ThemeSpec},
+};
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // stories fetched per request
+    pub page_size: u32,
+    // seconds between syncs for the daemon and `tail`
+    pub poll_interval: u64,
+    // strftime format for dates in the ui, shown in local time
+    pub date_format: String,
+    // funding instrument id to pay with unless another is picked
+    pub default_funding_source: Option<String>,
+    // who can see payments we send
+    pub audience: Audience,
+    pub user_agent: String,
+    pub layout: Layout,
+    // dark, light, high-contrast or one of `themes`
+    pub theme: String,
+    pub themes: HashMap<String, ThemeSpec>,
+    // default or vim
+    pub keymap: String,
+    // action name to the keys that replace the keymap's, e.g. quit = ["ctrl-q"]
+    pub keys: HashMap<String, Vec<String>>,
+    // clicks and scrolling in the ui. off leaves the terminal's own text selection working
+    pub mouse: bool,
+    // run by the daemon only
+    pub hooks: Hooks,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layout {
+    // percent of the width taken by the sidebar
+    pub sidebar_width: u16,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            page_size: 30,
+            poll_interval: 60,
+            date_format: "%Y-%m-%d %H:%M".to_string(),
+            default_funding_source: None,
+            audience: Audience::Private,
+            user_agent: "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:109.0) Gecko/20100101 Firefox/113.0".to_string(),
+            layout: Layout::default(),
+            theme: "dark".to_string(),
+            themes: HashMap::new(),
+            keymap: "default".to_string(),
+            keys: HashMap::new(),
+            mouse: true,
+            hooks: Hooks::default(),
+        }
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self { sidebar_width: 10 }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, String),
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => {
+                write!(f, "failed to read config {}! {e}", path.display())
+            }
+            ConfigError::Parse(path, e) => {
+                write!(f, "failed to parse config {}! {e}", path.display())
+            }
+            ConfigError::Invalid(path, e) => write!(f, "bad config {}! {e}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// $XDG_CONFIG_HOME/venmo-tui/config.toml, falling back to ~/.config
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .map(|dir| dir.join("venmo-tui").join("config.toml"))
+}
+
+impl Config {
+    pub fn from_toml(path: &Path, text: &str) -> Result<Self, ConfigError> {
+        let config: Config =
+            toml::from_str(text).map_err(|e| ConfigError::Parse(path.into(), e.to_string()))?;
+        config
+            .validate()
+            .map_err(|e| ConfigError::Invalid(path.into(), e))?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=100).contains(&self.page_size) {
+            return Err(format!(
+                "page_size must be between 1 and 100, not {}",
+                self.page_size
+            ));
+        }
+        if self.poll_interval < 5 {
+            return Err(format!(
+                "poll_interval must be at least 5 seconds, not {}",
+                self.poll_interval
+            ));
+        }
+        if StrftimeItems::new(&self.date_format).any(|i| matches!(i, Item::Error)) {
+            return Err(format!(
+                "date_format '{}' isn't a valid strftime format",
+                self.date_format
+            ));
+        }
+        if !(5..=50).contains(&self.layout.sidebar_width) {
+            return Err(format!(
+                "layout.sidebar_width must be between 5 and 50 percent, not {}",
+                self.layout.sidebar_width
+            ));
+        }
+        Theme::resolve(&self.theme, &self.themes)?;
+        Keymap::resolve(&self.keymap, &self.keys)?;
+        Ok(())
+    }
+
+    // an explicit path has to exist, the default one doesn't
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Config::default())
+            }
+            Err(e) => return Err(ConfigError::Read(path, e)),
+        };
+
+        Config::from_toml(&path, &text)
+    }
+}
+
+// called once at startup, before anything reads the config
+pub fn init(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
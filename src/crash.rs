@@ -0,0 +1,175 @@
+use std::{
+    backtrace::Backtrace,
+    fs,
+    io::{self, Write},
+    panic::PanicHookInfo,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use chrono::Local;
+use crossterm::{
+    cursor::Show,
+    event::DisableMouseCapture,
+    terminal::{disable_raw_mode, LeaveAlternateScreen},
+};
+use regex::Regex;
+
+// what the ui was doing, for the crash report
+struct Context {
+    page: &'static str,
+    api_call: Option<&'static str>,
+}
+
+static CONTEXT: Mutex<Context> = Mutex::new(Context {
+    page: "startup",
+    api_call: None,
+});
+
+pub fn set_page(page: &'static str) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        context.page = page;
+    }
+}
+
+// called at the start of each request to venmo
+pub fn record_api_call(name: &'static str) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        context.api_call = Some(name);
+    }
+}
+
+// put the terminal back the way we found it. safe to call more than once
+pub fn restore_terminal() -> io::Result<()> {
+    disable_raw_mode()?;
+    crossterm::execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        Show
+    )
+}
+
+// $XDG_STATE_HOME/venmo-tui, falling back to ~/.local/state and then the temp dir
+fn report_dir() -> PathBuf {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("venmo-tui")
+}
+
+// blank out anything that looks like a credential
+fn redact(text: &str) -> String {
+    let patterns = [
+        r"(?i)(bearer\s+)[^\s,;]+",
+        r#"(?i)((?:access_token|api_access_token|device[-_]?id|csrf|xsrf|token|password|cookie)["']?\s*[:=]\s*["']?)[^"'\s,;&]+"#,
+    ];
+    patterns.iter().fold(text.to_string(), |text, pattern| {
+        Regex::new(pattern)
+            .expect("failed to create regex")
+            .replace_all(&text, "${1}[redacted]")
+            .into_owned()
+    })
+}
+
+fn write_report(info: &PanicHookInfo) -> io::Result<PathBuf> {
+    let (page, api_call) = match CONTEXT.lock() {
+        Ok(c) => (c.page, c.api_call),
+        Err(_) => ("unknown", None),
+    };
+
+    let dir = report_dir();
+    fs::create_dir_all(&dir)?;
+    let now = Local::now();
+    // the pid keeps two crashes in the same second, e.g. the daemon and the tui, apart
+    let path = dir.join(format!(
+        "crash-{}-{}.txt",
+        now.format("%Y%m%d-%H%M%S"),
+        std::process::id()
+    ));
+
+    let mut file = fs::File::create(&path)?;
+    writeln!(
+        file,
+        "venmo-tui {} crashed at {}",
+        env!("CARGO_PKG_VERSION"),
+        now.to_rfc3339()
+    )?;
+    writeln!(file, "page: {page}")?;
+    writeln!(file, "last api call: {}", api_call.unwrap_or("none"))?;
+    writeln!(file, "panic: {}", redact(&info.to_string()))?;
+    writeln!(file)?;
+    writeln!(file, "{}", redact(&Backtrace::force_capture().to_string()))?;
+    Ok(path)
+}
+
+// restore the terminal before any panic message is printed, and leave a crash report behind
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+        match write_report(info) {
+            Ok(path) => eprintln!("crash report written to {}", path.display()),
+            Err(e) => eprintln!("failed to write crash report! {e}"),
+        }
+    }));
+}
+
+// a kill or a closed terminal would otherwise leave it in raw mode
+pub fn handle_signals() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async {
+        let (Ok(mut term), Ok(mut hangup), Ok(mut interrupt)) = (
+            signal(SignalKind::terminate()),
+            signal(SignalKind::hangup()),
+            signal(SignalKind::interrupt()),
+        ) else {
+            return;
+        };
+
+        let code = tokio::select! {
+            _ = term.recv() => 143,
+            _ = hangup.recv() => 129,
+            _ = interrupt.recv() => 130,
+        };
+        let _ = restore_terminal();
+        std::process::exit(code);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_bearer_tokens() {
+        assert_eq!(
+            redact("request failed: Authorization: Bearer abc123.def, retrying"),
+            "request failed: Authorization: Bearer [redacted], retrying"
+        );
+    }
+
+    #[test]
+    fn redacts_access_tokens_in_json() {
+        let text = r#"{"access_token": "abc123", "user": {"username": "bob"}}"#;
+        assert_eq!(
+            redact(text),
+            r#"{"access_token": "[redacted]", "user": {"username": "bob"}}"#
+        );
+    }
+
+    #[test]
+    fn redacts_device_ids() {
+        assert_eq!(
+            redact("headers: device-id=1234-5678-abcd; user-agent=venmo"),
+            "headers: device-id=[redacted]; user-agent=venmo"
+        );
+        assert_eq!(
+            redact(r#"{"device_id":"1234-5678-abcd"}"#),
+            r#"{"device_id":"[redacted]"}"#
+        );
+    }
+}
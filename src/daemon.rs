@@ -0,0 +1,681 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt, fs, io,
+    os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDate;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixListener, UnixStream,
+    },
+    sync::{broadcast, mpsc, Mutex},
+    task::JoinHandle,
+};
+
+use crate::{
+    api::{Api, ApiError, PaymentType},
+    config,
+    hooks::Hooks,
+    search::Query,
+    types::{Person, Story},
+};
+
+// how long a prepared payment waits for its `confirm`
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(120);
+
+// json-rpc error codes, the last two from the range reserved for servers
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+pub const API_ERROR: i64 = -32000;
+const BAD_TOKEN: i64 = -32001;
+
+// $XDG_RUNTIME_DIR/venmo-tui.sock, or a directory of our own in the temp dir when
+// there's no runtime dir
+pub fn socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join(format!("venmo-tui-{}", uid())))
+        .join("venmo-tui.sock")
+}
+
+fn uid() -> u32 {
+    // never fails
+    unsafe { libc::geteuid() }
+}
+
+// anyone who can reach the socket can move money, and anyone who can replace it can
+// pose as the daemon, so its directory has to be ours and closed to everyone else
+fn check_private_dir(dir: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(dir)?;
+    if !meta.is_dir() || meta.uid() != uid() || meta.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{} must be a directory only this user can access",
+                dir.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn socket_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new("."))
+}
+
+// make sure the socket's directory is private before anything is bound in it
+fn prepare_socket_dir(path: &Path) -> io::Result<()> {
+    let dir = socket_dir(path);
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+    check_private_dir(dir)
+}
+
+// a socket someone else could have put there isn't trusted
+fn check_socket(path: &Path) -> io::Result<()> {
+    check_private_dir(socket_dir(path))?;
+    if fs::symlink_metadata(path)?.uid() != uid() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} belongs to another user", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl From<ApiError> for RpcError {
+    fn from(e: ApiError) -> Self {
+        RpcError::new(API_ERROR, e.to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+// a response, or a notification when there's a method
+#[derive(Deserialize)]
+struct Response {
+    id: Option<Value>,
+    result: Option<Value>,
+    error: Option<RpcError>,
+    method: Option<String>,
+    params: Option<Value>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct StoriesParams {
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    // search syntax, see `search::Query`
+    query: String,
+    limit: Option<usize>,
+    // page back to the first story, rather than just as far as `since`
+    all: bool,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct EligibilityParams {
+    amount_cents: u32,
+    #[serde(default)]
+    note: String,
+    user_id: String,
+}
+
+#[derive(Deserialize)]
+struct FavouriteParams {
+    person: Person,
+}
+
+#[derive(Deserialize)]
+struct DefaultSourceParams {
+    #[serde(default)]
+    id: Option<String>,
+}
+
+// what a tui using the daemon keeps a copy of
+#[derive(Serialize, Deserialize, Default)]
+pub struct Settings {
+    pub favourites: Vec<Person>,
+    // picked on the wallet page, not the config's
+    pub default_source: Option<String>,
+}
+
+// the outcome of paging back once
+#[derive(Serialize, Deserialize)]
+pub struct Older {
+    pub added: usize,
+    pub reached_end: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PaymentParams {
+    pub handle: String,
+    // the recipient's venmo id when the caller already picked them, so the handle isn't
+    // searched for again and can't land on someone else
+    #[serde(default)]
+    pub user_id: Option<String>,
+    pub amount_cents: u32,
+    #[serde(default)]
+    pub note: String,
+    // a funding instrument id, payments only
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+// a payment waiting for its confirmation token
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Prepared {
+    pub token: String,
+    pub payment_type: PaymentType,
+    pub recipient: Person,
+    pub amount_cents: u32,
+    pub note: String,
+    pub expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct ConfirmParams {
+    token: String,
+}
+
+struct Pending {
+    prepared: Prepared,
+    source: Option<String>,
+    expires: Instant,
+}
+
+struct State {
+    api: Mutex<Api>,
+    pending: Mutex<HashMap<String, Pending>>,
+    stories: broadcast::Sender<Story>,
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn result<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(API_ERROR, e.to_string()))
+}
+
+async fn stories(state: &State, p: StoriesParams) -> Result<Value, RpcError> {
+    let mut query =
+        Query::parse(&p.query).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+    query.after = p.since.or(query.after);
+    query.before = p.until.or(query.before);
+
+    let mut api = state.api.lock().await;
+    if p.all {
+        api.load_all_stories(config::get().page_size).await?;
+    } else if let Some(since) = query.after {
+        api.load_stories_since(since, config::get().page_size)
+            .await?;
+    }
+
+    let stories = api
+        .cache
+        .stories()
+        .into_iter()
+        .filter(|s| query.matches(s))
+        .take(p.limit.unwrap_or(usize::MAX))
+        .collect::<Vec<_>>();
+    result(stories)
+}
+
+async fn prepare(
+    state: &State,
+    payment_type: PaymentType,
+    p: PaymentParams,
+) -> Result<Value, RpcError> {
+    let mut api = state.api.lock().await;
+    let recipient = match p.user_id {
+        Some(id) => Person {
+            id,
+            display_name: p.handle.clone(),
+            handle: p.handle.clone(),
+            first_name: None,
+            last_name: None,
+            is_friend: None,
+            avatar: None,
+        },
+        None => api.find_person(&p.handle).await?,
+    };
+    // no source means the same default the tui pays with
+    let source = match payment_type {
+        PaymentType::Pay => p.source.or_else(|| api.default_funding_source()),
+        PaymentType::Request => None,
+    };
+    drop(api);
+
+    let prepared = Prepared {
+        token: uuid::Uuid::new_v4().to_string(),
+        payment_type,
+        recipient,
+        amount_cents: p.amount_cents,
+        note: p.note,
+        expires_in: CONFIRM_TIMEOUT.as_secs(),
+    };
+
+    let mut pending = state.pending.lock().await;
+    pending.retain(|_, p| p.expires > Instant::now());
+    pending.insert(
+        prepared.token.clone(),
+        Pending {
+            prepared: prepared.clone(),
+            source,
+            expires: Instant::now() + CONFIRM_TIMEOUT,
+        },
+    );
+
+    result(prepared)
+}
+
+async fn confirm(state: &State, p: ConfirmParams) -> Result<Value, RpcError> {
+    let pending = match state.pending.lock().await.remove(&p.token) {
+        Some(pending) if pending.expires > Instant::now() => pending,
+        Some(_) => return Err(RpcError::new(BAD_TOKEN, "confirmation token expired")),
+        None => return Err(RpcError::new(BAD_TOKEN, "unknown confirmation token")),
+    };
+    let prepared = pending.prepared;
+
+    let resp = state
+        .api
+        .lock()
+        .await
+        .submit_payment(
+            prepared.amount_cents,
+            &prepared.note,
+            &prepared.recipient,
+            prepared.payment_type,
+            pending.source.as_deref(),
+            None,
+        )
+        .await?;
+    result(resp)
+}
+
+// forward new stories to a subscribed connection as `story` notifications
+fn subscribe(state: &State, out: mpsc::UnboundedSender<Value>) -> JoinHandle<()> {
+    let mut stories = state.stories.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match stories.recv().await {
+                Ok(story) => {
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": "story",
+                        "params": story,
+                    });
+                    if out.send(notification).is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    })
+}
+
+async fn handle(
+    state: &State,
+    request: Request,
+    out: &mpsc::UnboundedSender<Value>,
+    subscriptions: &mut Vec<JoinHandle<()>>,
+) -> Result<Value, RpcError> {
+    match request.method.as_str() {
+        "balance" => {
+            let mut api = state.api.lock().await;
+            let identity = if api.offline {
+                api.identity.clone().unwrap()
+            } else {
+                api.get_profile().await?
+            };
+            result(identity)
+        }
+        "stories" => stories(state, params(request.params)?).await,
+        "search" => {
+            let p: SearchParams = params(request.params)?;
+            result(state.api.lock().await.search_people(&p.name).await?)
+        }
+        "instruments" => result(state.api.lock().await.get_funding_instruments().await?),
+        "older" => {
+            let mut api = state.api.lock().await;
+            let added = api.load_older_stories(config::get().page_size).await?;
+            result(Older {
+                added,
+                reached_end: api.cache.reached_end(),
+            })
+        }
+        "eligibility" => {
+            let p: EligibilityParams = params(request.params)?;
+            let mut api = state.api.lock().await;
+            result(
+                api.fetch_eligibility(p.amount_cents, &p.note, &p.user_id)
+                    .await?,
+            )
+        }
+        "settings" => {
+            let api = state.api.lock().await;
+            result(Settings {
+                favourites: api.cache.favourites(),
+                default_source: api.cache.default_funding_source(),
+            })
+        }
+        "toggle_favourite" => {
+            let p: FavouriteParams = params(request.params)?;
+            result(state.api.lock().await.toggle_favourite(&p.person).await?)
+        }
+        "set_default_source" => {
+            let p: DefaultSourceParams = params(request.params)?;
+            let mut api = state.api.lock().await;
+            api.set_default_funding_source(p.id.as_deref()).await?;
+            result(true)
+        }
+        "pay" => prepare(state, PaymentType::Pay, params(request.params)?).await,
+        "request" => prepare(state, PaymentType::Request, params(request.params)?).await,
+        "confirm" => confirm(state, params(request.params)?).await,
+        "subscribe" => {
+            subscriptions.push(subscribe(state, out.clone()));
+            result(true)
+        }
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method '{method}'"),
+        )),
+    }
+}
+
+async fn handle_connection(state: Arc<State>, stream: UnixStream) {
+    let (reader, mut writer) = stream.into_split();
+
+    // responses and notifications share the socket, so one task does all the writing
+    let (out, mut messages) = mpsc::unbounded_channel::<Value>();
+    let writing = tokio::spawn(async move {
+        while let Some(message) = messages.recv().await {
+            let line = format!("{message}\n");
+            if writer.write_all(line.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut subscriptions = vec![];
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (id, outcome) = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => (
+                request.id.clone(),
+                handle(&state, request, &out, &mut subscriptions).await,
+            ),
+            Err(e) => (Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))),
+        };
+
+        let response = match outcome {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+        };
+        if out.send(response).is_err() {
+            break;
+        }
+    }
+
+    subscriptions.iter().for_each(|s| s.abort());
+    drop(out);
+    let _ = writing.await;
+}
+
+// sync on an interval, announcing new stories oldest first and running hooks
+async fn poll(state: Arc<State>, hooks: Hooks) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config::get().poll_interval));
+    let mut was_below = false;
+    loop {
+        interval.tick().await;
+
+        let mut api = state.api.lock().await;
+        // a first sync pulls in a page of old stories, which aren't news to hooks or subscribers
+        let first_sync = api.cache.is_empty();
+        let (new, changed) = match api.sync_story_changes(config::get().page_size).await {
+            Ok(synced) => (
+                api.cache.stories().into_iter().take(synced.added).collect(),
+                synced.changed,
+            ),
+            Err(e) => {
+                eprintln!("{e}");
+                (vec![], vec![])
+            }
+        };
+
+        let me = api.identity.clone().unwrap();
+        if !first_sync {
+            for story in new.into_iter().rev() {
+                hooks.story(&story, &me);
+                // no subscribers is fine
+                let _ = state.stories.send(story);
+            }
+        }
+        for story in changed.iter() {
+            hooks.status_changed(story, &me);
+        }
+
+        // only fire when the balance crosses the threshold, not on every poll under it
+        if hooks.balance_threshold.is_some() {
+            match api.get_profile().await {
+                Ok(me) => {
+                    let below = hooks.is_below(&me);
+                    if below && !was_below {
+                        hooks.balance(&me);
+                    }
+                    was_below = below;
+                }
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+    }
+}
+
+pub async fn serve(api: Api, path: &Path) -> Result<(), Box<dyn Error>> {
+    if api.offline {
+        return Err(
+            ApiError::Offline("the daemon needs a connection to venmo.".to_string()).into(),
+        );
+    }
+
+    prepare_socket_dir(path)?;
+
+    // a socket left behind by a daemon that didn't shut down cleanly would make bind fail
+    if UnixStream::connect(path).await.is_ok() {
+        return Err(format!("a daemon is already listening on {}", path.display()).into());
+    }
+    let _ = fs::remove_file(path);
+
+    // the directory already keeps everyone else out, this is belt and braces
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+
+    let (stories, _) = broadcast::channel(64);
+    let state = Arc::new(State {
+        api: Mutex::new(api),
+        pending: Mutex::new(HashMap::new()),
+        stories,
+    });
+    tokio::spawn(poll(state.clone(), config::get().hooks.clone()));
+
+    eprintln!("listening on {}", path.display());
+
+    let outcome = loop {
+        tokio::select! {
+            conn = listener.accept() => match conn {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle_connection(state.clone(), stream));
+                }
+                Err(e) => break Err(e.into()),
+            },
+            _ = tokio::signal::ctrl_c() => break Ok(()),
+        }
+    };
+
+    let _ = fs::remove_file(path);
+    outcome
+}
+
+// a connection to a running daemon
+pub struct Client {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    next_id: u64,
+    // stories announced while waiting on a call
+    stories: VecDeque<Story>,
+}
+
+impl Client {
+    // `NotFound`/`ConnectionRefused` when no daemon is running, `PermissionDenied` when
+    // the socket isn't one this user can trust
+    pub async fn connect(path: &Path) -> io::Result<Self> {
+        check_socket(path)?;
+        let (reader, writer) = UnixStream::connect(path).await?.into_split();
+        Ok(Client {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            next_id: 0,
+            stories: VecDeque::new(),
+        })
+    }
+
+    async fn read(&mut self) -> Result<Response, Box<dyn Error>> {
+        let line = self
+            .lines
+            .next_line()
+            .await?
+            .ok_or("the daemon closed the connection")?;
+        let response = serde_json::from_str::<Response>(&line)?;
+
+        if response.method.as_deref() == Some("story") {
+            if let Some(params) = response.params.clone() {
+                self.stories.push_back(serde_json::from_value(params)?);
+            }
+        }
+        Ok(response)
+    }
+
+    // the next story announced after `subscribe`
+    pub async fn next_story(&mut self) -> Result<Story, Box<dyn Error>> {
+        loop {
+            if let Some(story) = self.stories.pop_front() {
+                return Ok(story);
+            }
+            self.read().await?;
+        }
+    }
+
+    pub async fn call<T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, Box<dyn Error>> {
+        self.next_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id,
+            "method": method,
+            "params": params,
+        });
+        self.writer
+            .write_all(format!("{request}\n").as_bytes())
+            .await?;
+
+        loop {
+            let response = self.read().await?;
+
+            // skip notifications and anything else that isn't our answer
+            if response.id != Some(json!(self.next_id)) {
+                continue;
+            }
+            if let Some(error) = response.error {
+                return Err(error.into());
+            }
+            return Ok(serde_json::from_value(
+                response.result.unwrap_or(Value::Null),
+            )?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_dir_must_be_private() {
+        let dir = std::env::temp_dir().join(format!("venmo-tui-test-{}", uuid::Uuid::new_v4()));
+        let socket = dir.join("venmo-tui.sock");
+
+        prepare_socket_dir(&socket).unwrap();
+        assert_eq!(fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+        check_private_dir(&dir).unwrap();
+
+        // another user could swap the socket out
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+        let e = prepare_socket_dir(&socket).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            check_socket(&socket).unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+
+        fs::remove_dir(&dir).unwrap();
+    }
+}
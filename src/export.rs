@@ -0,0 +1,367 @@
+use std::{fmt, io, io::Write, str::FromStr};
+
+use chrono::{Local, NaiveDateTime};
+use serde::Serialize;
+
+use crate::types::{format_cents, Identity, Story};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Format {
+    Csv,
+    Json,
+    Ofx,
+    Qif,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [Format::Csv, Format::Json, Format::Ofx, Format::Qif];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "jsonl",
+            Format::Ofx => "ofx",
+            Format::Qif => "qif",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Csv => "CSV",
+            Format::Json => "JSON lines",
+            Format::Ofx => "OFX",
+            Format::Qif => "QIF",
+        })
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "json" | "jsonl" => Ok(Format::Json),
+            "ofx" => Ok(Format::Ofx),
+            "qif" => Ok(Format::Qif),
+            _ => Err(format!("unknown format '{s}' (use csv, json, ofx or qif)")),
+        }
+    }
+}
+
+// a story flattened into the fields every format needs
+#[derive(Serialize)]
+pub struct Record<'a> {
+    pub id: &'a str,
+    pub date: Option<NaiveDateTime>,
+    pub sub_type: &'static str,
+    pub amount: String,
+    pub amount_cents: Option<i64>,
+    pub sender: Option<&'a str>,
+    pub sender_name: Option<&'a str>,
+    pub receiver: Option<&'a str>,
+    pub receiver_name: Option<&'a str>,
+    pub counterparty: Option<&'a str>,
+    pub note: &'a str,
+}
+
+impl<'a> Record<'a> {
+    pub fn new(story: &'a Story) -> Self {
+        let cents = story.amount_cents();
+        Record {
+            id: &story.id,
+            date: story.timestamp(),
+            sub_type: story.title.payload.sub_type.as_str(),
+            amount: cents.map(format_cents).unwrap_or_default(),
+            amount_cents: cents,
+            sender: story.title.sender.as_ref().map(|p| p.username.as_str()),
+            sender_name: story.title.sender.as_ref().map(|p| p.display_name.as_str()),
+            receiver: story.title.receiver.as_ref().map(|p| p.username.as_str()),
+            receiver_name: story
+                .title
+                .receiver
+                .as_ref()
+                .map(|p| p.display_name.as_str()),
+            counterparty: story.counterparty().map(|p| p.display_name.as_str()),
+            note: story.note.content.as_deref().unwrap_or_default(),
+        }
+    }
+}
+
+// e.g. venmo-2026-10-18.csv
+pub fn default_file_name(format: Format) -> String {
+    format!(
+        "venmo-{}.{}",
+        Local::now().format("%Y-%m-%d"),
+        format.extension()
+    )
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\n', '\r'], " ")
+}
+
+fn write_csv<W: Write>(out: &mut W, stories: &[Story]) -> io::Result<()> {
+    writeln!(
+        out,
+        "id,date,type,amount,sender,sender_name,receiver,receiver_name,note"
+    )?;
+
+    for story in stories {
+        let r = Record::new(story);
+        let fields = [
+            r.id.to_string(),
+            r.date.map(|d| d.to_string()).unwrap_or_default(),
+            r.sub_type.to_string(),
+            r.amount,
+            r.sender.unwrap_or_default().to_string(),
+            r.sender_name.unwrap_or_default().to_string(),
+            r.receiver.unwrap_or_default().to_string(),
+            r.receiver_name.unwrap_or_default().to_string(),
+            r.note.to_string(),
+        ];
+
+        writeln!(
+            out,
+            "{}",
+            fields
+                .iter()
+                .map(|f| csv_field(f))
+                .collect::<Vec<_>>()
+                .join(",")
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_json<W: Write>(out: &mut W, stories: &[Story]) -> io::Result<()> {
+    for story in stories {
+        serde_json::to_writer(&mut *out, &Record::new(story))?;
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn write_ofx<W: Write>(out: &mut W, stories: &[Story], identity: &Identity) -> io::Result<()> {
+    const OFX_DATE: &str = "%Y%m%d%H%M%S";
+
+    let now = Local::now().format(OFX_DATE).to_string();
+    let dates = stories.iter().filter_map(|s| s.timestamp());
+    let start = dates.clone().min().map(|d| d.format(OFX_DATE).to_string());
+    let end = dates.max().map(|d| d.format(OFX_DATE).to_string());
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<?OFX OFXHEADER="200" VERSION="220" SECURITY="NONE" OLDFILEUID="NONE" NEWFILEUID="NONE"?>"#
+    )?;
+    writeln!(out, "<OFX>")?;
+    writeln!(out, "<SIGNONMSGSRSV1><SONRS>")?;
+    writeln!(
+        out,
+        "<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>"
+    )?;
+    writeln!(out, "<DTSERVER>{now}</DTSERVER><LANGUAGE>ENG</LANGUAGE>")?;
+    writeln!(out, "</SONRS></SIGNONMSGSRSV1>")?;
+    writeln!(out, "<BANKMSGSRSV1><STMTTRNRS>")?;
+    writeln!(
+        out,
+        "<TRNUID>0</TRNUID><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>"
+    )?;
+    writeln!(out, "<STMTRS><CURDEF>USD</CURDEF>")?;
+    writeln!(
+        out,
+        "<BANKACCTFROM><BANKID>VENMO</BANKID><ACCTID>{}</ACCTID><ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>",
+        xml_escape(&identity.handle)
+    )?;
+    writeln!(
+        out,
+        "<BANKTRANLIST><DTSTART>{}</DTSTART><DTEND>{}</DTEND>",
+        start.as_deref().unwrap_or(&now),
+        end.as_deref().unwrap_or(&now)
+    )?;
+
+    for story in stories {
+        let r = Record::new(story);
+        let cents = r.amount_cents.unwrap_or_default();
+
+        writeln!(out, "<STMTTRN>")?;
+        writeln!(
+            out,
+            "<TRNTYPE>{}</TRNTYPE>",
+            if cents < 0 { "DEBIT" } else { "CREDIT" }
+        )?;
+        writeln!(
+            out,
+            "<DTPOSTED>{}</DTPOSTED>",
+            r.date
+                .map(|d| d.format(OFX_DATE).to_string())
+                .unwrap_or_else(|| now.clone())
+        )?;
+        writeln!(out, "<TRNAMT>{}</TRNAMT>", format_cents(cents))?;
+        writeln!(out, "<FITID>{}</FITID>", xml_escape(r.id))?;
+        if let Some(name) = r.counterparty {
+            // OFX caps payee names at 32 characters
+            let name = name.chars().take(32).collect::<String>();
+            writeln!(out, "<NAME>{}</NAME>", xml_escape(&name))?;
+        }
+        if !r.note.is_empty() {
+            writeln!(out, "<MEMO>{}</MEMO>", xml_escape(&single_line(r.note)))?;
+        }
+        writeln!(out, "</STMTTRN>")?;
+    }
+
+    writeln!(out, "</BANKTRANLIST>")?;
+    writeln!(
+        out,
+        "<LEDGERBAL><BALAMT>{:.2}</BALAMT><DTASOF>{now}</DTASOF></LEDGERBAL>",
+        identity.balance.user_balance.value
+    )?;
+    writeln!(out, "</STMTRS></STMTTRNRS></BANKMSGSRSV1>")?;
+    writeln!(out, "</OFX>")?;
+
+    Ok(())
+}
+
+fn write_qif<W: Write>(out: &mut W, stories: &[Story]) -> io::Result<()> {
+    writeln!(out, "!Type:Bank")?;
+
+    for story in stories {
+        let r = Record::new(story);
+
+        if let Some(date) = r.date {
+            writeln!(out, "D{}", date.format("%m/%d/%Y"))?;
+        }
+        writeln!(out, "T{}", r.amount)?;
+        if let Some(name) = r.counterparty {
+            writeln!(out, "P{}", single_line(name))?;
+        }
+        if !r.note.is_empty() {
+            writeln!(out, "M{}", single_line(r.note))?;
+        }
+        writeln!(out, "^")?;
+    }
+
+    Ok(())
+}
+
+pub fn export<W: Write>(
+    out: &mut W,
+    format: Format,
+    stories: &[Story],
+    identity: &Identity,
+) -> io::Result<()> {
+    match format {
+        Format::Csv => write_csv(out, stories),
+        Format::Json => write_json(out, stories),
+        Format::Ofx => write_ofx(out, stories, identity),
+        Format::Qif => write_qif(out, stories),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> Identity {
+        serde_json::from_value(serde_json::json!({
+            "isDenylisted": false,
+            "isSuspended": false,
+            "type": "personal",
+            "avatar": { "url": "" },
+            "displayName": "Me",
+            "handle": "me",
+            "id": "me-id",
+            "balance": { "userBalance": { "value": 12.5 } },
+        }))
+        .unwrap()
+    }
+
+    fn render(format: Format, stories: &[Story]) -> String {
+        let mut out = vec![];
+        export(&mut out, format, stories, &identity()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_quotes_fields_that_need_it() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+
+        let story = Story::fixture(
+            "1",
+            "2026-01-15T12:00:00",
+            "- $25.00",
+            "bob",
+            "pizza, \"extra\"",
+        );
+        let csv = render(Format::Csv, &[story]);
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.starts_with("1,"));
+        assert!(row.ends_with(",-25.00,me,ME,bob,BOB,\"pizza, \"\"extra\"\"\""));
+    }
+
+    #[test]
+    fn ofx_escapes_markup() {
+        let story = Story::fixture("<1>", "2026-01-15T12:00:00", "+ $5.00", "bob", "a & b\n<c>");
+        let ofx = render(Format::Ofx, &[story]);
+        assert!(ofx.contains("<TRNTYPE>CREDIT</TRNTYPE>"));
+        assert!(ofx.contains("<TRNAMT>5.00</TRNAMT>"));
+        assert!(ofx.contains("<FITID>&lt;1&gt;</FITID>"));
+        assert!(ofx.contains("<MEMO>a &amp; b &lt;c&gt;</MEMO>"));
+        assert!(ofx.contains("<BALAMT>12.50</BALAMT>"));
+    }
+
+    #[test]
+    fn qif_keeps_fields_on_one_line() {
+        let story = Story::fixture(
+            "1",
+            "2026-01-15T12:00:00",
+            "- $25.00",
+            "bob",
+            "line one\r\nline two",
+        );
+        let qif = render(Format::Qif, &[story]);
+        assert_eq!(
+            qif.lines().collect::<Vec<_>>(),
+            vec![
+                "!Type:Bank",
+                "D01/15/2026",
+                "T-25.00",
+                "PBOB",
+                "Mline one  line two",
+                "^"
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines_are_records() {
+        let story = Story::fixture("1", "2026-01-15T12:00:00", "- $25.00", "bob", "pizza");
+        let json = render(Format::Json, &[story]);
+        let record = serde_json::from_str::<serde_json::Value>(json.trim()).unwrap();
+        assert_eq!(record["amount_cents"], -2500);
+        assert_eq!(record["counterparty"], "BOB");
+    }
+}
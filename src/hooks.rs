@@ -0,0 +1,232 @@
+use std::{fmt, process::Stdio};
+
+use serde::Deserialize;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::types::{format_cents, Identity, Story, StorySubType};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Event {
+    // someone paid us
+    PaymentReceived,
+    // someone requested money from us
+    RequestReceived,
+    // a payment of ours went through
+    PaymentSettled,
+    // the balance dropped under the threshold
+    BalanceBelow,
+}
+
+impl Event {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Event::PaymentReceived => "payment_received",
+            Event::RequestReceived => "request_received",
+            Event::PaymentSettled => "payment_settled",
+            Event::BalanceBelow => "balance_below",
+        }
+    }
+
+    // what a newly seen story means for us, if anything. only p2p stories count
+    pub fn of(story: &Story, me: &Identity) -> Option<Event> {
+        if story.title.payload.sub_type != StorySubType::P2p {
+            return None;
+        }
+
+        let charge = story.title.payload.action.as_deref() == Some("charge");
+        let from_me = story.title.sender.as_ref().is_some_and(|s| s.id == me.id);
+        let settled = story.status.as_deref() == Some("settled");
+        match story.amount_cents()? {
+            _ if charge && !from_me => Some(Event::RequestReceived),
+            cents if cents > 0 => Some(Event::PaymentReceived),
+            // a pending payment settles later, see Hooks::status_changed
+            cents if cents < 0 && settled => Some(Event::PaymentSettled),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// the [hooks] config table: shell commands to run per event. each gets the story (or identity, for
+// balance_below) as json on stdin, plus VENMO_* environment variables.
+// only `venmo-tui daemon` runs them, the tui and the other commands never do
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    pub payment_received: Vec<String>,
+    pub request_received: Vec<String>,
+    pub payment_settled: Vec<String>,
+    pub balance_below: Vec<String>,
+    // in dollars
+    pub balance_threshold: Option<f32>,
+}
+
+impl Hooks {
+    fn commands(&self, event: Event) -> &[String] {
+        match event {
+            Event::PaymentReceived => &self.payment_received,
+            Event::RequestReceived => &self.request_received,
+            Event::PaymentSettled => &self.payment_settled,
+            Event::BalanceBelow => &self.balance_below,
+        }
+    }
+
+    pub fn story(&self, story: &Story, me: &Identity) {
+        let Some(event) = Event::of(story, me) else {
+            return;
+        };
+
+        let cents = story.amount_cents().unwrap_or_default();
+        let counterparty = story.counterparty();
+        let env = vec![
+            ("VENMO_EVENT", event.to_string()),
+            ("VENMO_STORY_ID", story.id.clone()),
+            ("VENMO_DATE", story.date.clone()),
+            ("VENMO_AMOUNT", format_cents(cents)),
+            ("VENMO_AMOUNT_CENTS", cents.to_string()),
+            (
+                "VENMO_COUNTERPARTY",
+                counterparty.map(|p| p.username.clone()).unwrap_or_default(),
+            ),
+            (
+                "VENMO_COUNTERPARTY_NAME",
+                counterparty
+                    .map(|p| p.display_name.clone())
+                    .unwrap_or_default(),
+            ),
+            ("VENMO_NOTE", story.note.content.clone().unwrap_or_default()),
+        ];
+
+        let json = serde_json::to_string(story).expect("failed to serialize story");
+        for command in self.commands(event) {
+            run(event, command, json.clone(), env.clone());
+        }
+    }
+
+    // a story we'd already seen has a new status. only a payment of ours settling is news,
+    // the rest fired when the story first showed up
+    pub fn status_changed(&self, story: &Story, me: &Identity) {
+        if Event::of(story, me) == Some(Event::PaymentSettled) {
+            self.story(story, me);
+        }
+    }
+
+    pub fn is_below(&self, me: &Identity) -> bool {
+        self.balance_threshold
+            .is_some_and(|t| me.balance.user_balance.value < t)
+    }
+
+    pub fn balance(&self, me: &Identity) {
+        let env = vec![
+            ("VENMO_EVENT", Event::BalanceBelow.to_string()),
+            (
+                "VENMO_BALANCE",
+                format!("{:.2}", me.balance.user_balance.value),
+            ),
+            (
+                "VENMO_THRESHOLD",
+                format!("{:.2}", self.balance_threshold.unwrap_or_default()),
+            ),
+        ];
+
+        let json = serde_json::to_string(me).expect("failed to serialize identity");
+        for command in self.commands(Event::BalanceBelow) {
+            run(Event::BalanceBelow, command, json.clone(), env.clone());
+        }
+    }
+}
+
+// run `command` with sh in the background, logging failures to stderr
+fn run(event: Event, command: &str, stdin: String, env: Vec<(&'static str, String)>) {
+    let command = command.to_string();
+    tokio::spawn(async move {
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("failed to run {event} hook '{command}'! {e}");
+                return;
+            }
+        };
+
+        if let Some(mut input) = child.stdin.take() {
+            // the hook doesn't have to read it
+            let _ = input.write_all(stdin.as_bytes()).await;
+        }
+
+        match child.wait().await {
+            Ok(status) if !status.success() => {
+                eprintln!("{event} hook '{command}' exited with {status}")
+            }
+            Err(e) => eprintln!("failed to wait on {event} hook '{command}'! {e}"),
+            _ => {}
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn me() -> Identity {
+        serde_json::from_value(serde_json::json!({
+            "isDenylisted": false,
+            "isSuspended": false,
+            "type": "personal",
+            "avatar": { "url": "" },
+            "displayName": "Me",
+            "handle": "me",
+            "id": "me-id",
+            "balance": { "userBalance": { "value": 12.5 } },
+        }))
+        .unwrap()
+    }
+
+    fn story(amount: &str, action: &str, status: &str) -> Story {
+        let mut story = Story::fixture("1", "2026-01-15T12:00:00", amount, "bob", "");
+        story.title.payload.action = Some(action.to_string());
+        story.status = Some(status.to_string());
+        story
+    }
+
+    #[test]
+    fn payments_out_only_count_once_settled() {
+        let me = me();
+        assert_eq!(Event::of(&story("- $5.00", "pay", "pending"), &me), None);
+        assert_eq!(
+            Event::of(&story("- $5.00", "pay", "settled"), &me),
+            Some(Event::PaymentSettled)
+        );
+    }
+
+    #[test]
+    fn events_for_money_coming_in() {
+        let me = me();
+        assert_eq!(
+            Event::of(&story("+ $5.00", "pay", "settled"), &me),
+            Some(Event::PaymentReceived)
+        );
+        // bob sent the charge, so it's a request for our money
+        assert_eq!(
+            Event::of(&story("+ $5.00", "charge", "pending"), &me),
+            Some(Event::RequestReceived)
+        );
+        // and one we sent isn't
+        assert_eq!(Event::of(&story("- $5.00", "charge", "pending"), &me), None);
+
+        let mut transfer = story("+ $5.00", "pay", "settled");
+        transfer.title.payload.sub_type = StorySubType::StandardTransfer;
+        assert_eq!(Event::of(&transfer, &me), None);
+    }
+}
@@ -0,0 +1,272 @@
+use std::{collections::HashSet, fmt, io, io::Write, str::FromStr};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{
+    search::{Query, QueryError},
+    types::{format_cents, Story, StorySubType},
+};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum JournalFormat {
+    // ledger and hledger read the same syntax
+    Ledger,
+    Beancount,
+}
+
+impl FromStr for JournalFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ledger" | "hledger" => Ok(JournalFormat::Ledger),
+            "beancount" => Ok(JournalFormat::Beancount),
+            _ => Err(format!(
+                "unknown journal format '{s}' (use ledger, hledger or beancount)"
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Rule {
+    // a search query, e.g. "with:@alice rent"
+    pub query: String,
+    pub account: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct JournalRules {
+    // the venmo balance itself
+    pub account: String,
+    // where money goes/comes from when no rule matches
+    pub expense_account: String,
+    pub income_account: String,
+    // the other side of transfers to and from the bank
+    pub transfer_account: String,
+    pub currency: String,
+    // checked in order, the first match wins
+    pub rules: Vec<Rule>,
+}
+
+impl Default for JournalRules {
+    fn default() -> Self {
+        Self {
+            account: "Assets:Venmo".to_string(),
+            expense_account: "Expenses:Venmo".to_string(),
+            income_account: "Income:Venmo".to_string(),
+            transfer_account: "Assets:Bank".to_string(),
+            currency: "USD".to_string(),
+            rules: vec![],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RulesError {
+    Parse(String),
+    Query(String, QueryError),
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::Parse(e) => write!(f, "failed to parse journal rules! {e}"),
+            RulesError::Query(q, e) => write!(f, "bad journal rule query '{q}'! {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+// rules with their queries parsed, ready to categorize stories
+pub struct Categorizer {
+    rules: JournalRules,
+    queries: Vec<(Query, String)>,
+}
+
+impl Categorizer {
+    pub fn new(rules: JournalRules) -> Result<Self, RulesError> {
+        let queries = rules
+            .rules
+            .iter()
+            .map(|r| {
+                Query::parse(&r.query)
+                    .map(|q| (q, r.account.clone()))
+                    .map_err(|e| RulesError::Query(r.query.clone(), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { rules, queries })
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, RulesError> {
+        Self::new(toml::from_str(text).map_err(|e| RulesError::Parse(e.to_string()))?)
+    }
+
+    // the account on the other side of the venmo balance
+    fn account_for(&self, story: &Story, cents: i64) -> &str {
+        if let Some((_, account)) = self.queries.iter().find(|(q, _)| q.matches(story)) {
+            return account;
+        }
+
+        match story.title.payload.sub_type {
+            StorySubType::StandardTransfer => &self.rules.transfer_account,
+            _ if cents < 0 => &self.rules.expense_account,
+            _ => &self.rules.income_account,
+        }
+    }
+
+    pub fn write_entry<W: Write>(
+        &self,
+        out: &mut W,
+        format: JournalFormat,
+        story: &Story,
+    ) -> io::Result<()> {
+        let (Some(cents), Some(day)) = (story.amount_cents(), story.day()) else {
+            return Ok(());
+        };
+
+        let payee = story
+            .counterparty()
+            .map(|p| p.display_name.as_str())
+            .unwrap_or("Venmo")
+            .replace(['\n', '\r'], " ");
+        let note = story
+            .note
+            .content
+            .as_deref()
+            .unwrap_or_default()
+            .replace(['\n', '\r'], " ");
+        let other = self.account_for(story, cents);
+        let currency = &self.rules.currency;
+
+        writeln!(out)?;
+        match format {
+            JournalFormat::Ledger => {
+                writeln!(out, "{} * {payee}", day.format("%Y-%m-%d"))?;
+                writeln!(out, "    ; venmo-id: {}", story.id)?;
+                if !note.is_empty() {
+                    writeln!(out, "    ; {note}")?;
+                }
+                writeln!(out, "    {other}  {} {currency}", format_cents(-cents))?;
+                writeln!(out, "    {}", self.rules.account)?;
+            }
+            JournalFormat::Beancount => {
+                writeln!(
+                    out,
+                    "{} * \"{}\" \"{}\"",
+                    day.format("%Y-%m-%d"),
+                    beancount_escape(&payee),
+                    beancount_escape(&note)
+                )?;
+                writeln!(out, "  venmo_id: \"{}\"", story.id)?;
+                writeln!(
+                    out,
+                    "  {}  {} {currency}",
+                    self.rules.account,
+                    format_cents(cents)
+                )?;
+                writeln!(out, "  {other}  {} {currency}", format_cents(-cents))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// backslashes and quotes would end or mangle a beancount string
+fn beancount_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// story ids already written to a journal, from the `venmo-id`/`venmo_id` metadata
+pub fn synced_ids(journal: &str) -> HashSet<String> {
+    let regex = Regex::new(r#"venmo[-_]id:\s*"?([^\s"]+)"#).expect("failed to create regex");
+
+    regex
+        .captures_iter(journal)
+        .map(|c| c[1].to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(categorizer: &Categorizer, format: JournalFormat, story: &Story) -> String {
+        let mut out = vec![];
+        categorizer.write_entry(&mut out, format, story).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn finds_ids_from_either_format() {
+        let journal = r#"
+2026-01-15 * Bob
+    ; venmo-id: 111
+    Expenses:Venmo  25.00 USD
+    Assets:Venmo
+
+2026-01-16 * "Alice" "coffee"
+  venmo_id: "222"
+  Assets:Venmo  5.00 USD
+  Income:Venmo  -5.00 USD
+"#;
+        let ids = synced_ids(journal);
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains("111"));
+        assert!(ids.contains("222"));
+        assert!(synced_ids("2026-01-15 * Bob\n    ; not venmo\n").is_empty());
+    }
+
+    #[test]
+    fn written_entries_are_found_again() {
+        let categorizer = Categorizer::new(JournalRules::default()).unwrap();
+        let story = Story::fixture("333", "2026-01-15T12:00:00", "- $25.00", "bob", "pizza");
+
+        for format in [JournalFormat::Ledger, JournalFormat::Beancount] {
+            let ids = synced_ids(&entry(&categorizer, format, &story));
+            assert_eq!(ids.into_iter().collect::<Vec<_>>(), vec!["333"]);
+        }
+    }
+
+    #[test]
+    fn beancount_strings_are_escaped() {
+        let categorizer = Categorizer::new(JournalRules::default()).unwrap();
+        let story = Story::fixture(
+            "1",
+            "2026-01-15T12:00:00",
+            "- $25.00",
+            "bob",
+            "a \"quote\" and C:\\path\r\nnext",
+        );
+
+        let text = entry(&categorizer, JournalFormat::Beancount, &story);
+        assert_eq!(
+            text.lines().nth(1).unwrap(),
+            r#"2026-01-15 * "BOB" "a \"quote\" and C:\\path  next""#
+        );
+    }
+
+    #[test]
+    fn rules_pick_the_account() {
+        let categorizer = Categorizer::from_toml(
+            r#"
+            [[rules]]
+            query = "with:bob pizza"
+            account = "Expenses:Food"
+            "#,
+        )
+        .unwrap();
+        let pizza = Story::fixture("1", "2026-01-15T12:00:00", "- $25.00", "bob", "pizza");
+        let rent = Story::fixture("2", "2026-01-15T12:00:00", "+ $900.00", "alice", "rent");
+
+        let text = entry(&categorizer, JournalFormat::Ledger, &pizza);
+        assert!(text.contains("    Expenses:Food  25.00 USD\n"));
+        let text = entry(&categorizer, JournalFormat::Ledger, &rent);
+        assert!(text.contains("    Income:Venmo  -900.00 USD\n"));
+    }
+}
@@ -0,0 +1,618 @@
+use std::{collections::HashMap, fmt, sync::OnceLock};
+
+use tui_textarea::{Input, Key};
+
+use crate::config;
+
+static KEYMAP: OnceLock<Keymap> = OnceLock::new();
+
+pub const PRESETS: [&str; 2] = ["default", "vim"];
+
+// rows moved by page_down and page_up
+pub const PAGE_STEP: usize = 10;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    NextItem,
+    PrevItem,
+    FirstItem,
+    LastItem,
+    PageDown,
+    PageUp,
+    // between side by side things, e.g. the sidebar and the page or the pay buttons
+    Left,
+    Right,
+    Submit,
+    // leave the popup, input or page we're in
+    Back,
+    // leave the app from anywhere
+    Quit,
+    Search,
+    Export,
+    // pin or unpin a recipient as a favourite
+    Pin,
+    // overlays
+    Help,
+    Palette,
+    // commands, mostly run from the palette
+    Refresh,
+    GoHome,
+    GoTransactions,
+    GoAnalytics,
+    GoBalances,
+    GoWallet,
+    Pay,
+    SwitchAccount,
+    // only on one page or pane, see Action::PAGES
+    AllTime,
+    Month,
+    Quarter,
+    Year,
+    PrevPeriod,
+    NextPeriod,
+    CustomRange,
+    PeriodTransactions,
+    Sort,
+    SettleUp,
+    CopyId,
+    PayBack,
+    RequestAgain,
+}
+
+impl Action {
+    pub const ALL: [Action; 37] = [
+        Action::NextItem,
+        Action::PrevItem,
+        Action::FirstItem,
+        Action::LastItem,
+        Action::PageDown,
+        Action::PageUp,
+        Action::Left,
+        Action::Right,
+        Action::Submit,
+        Action::Back,
+        Action::Quit,
+        Action::Search,
+        Action::Export,
+        Action::Pin,
+        Action::Help,
+        Action::Palette,
+        Action::Refresh,
+        Action::GoHome,
+        Action::GoTransactions,
+        Action::GoAnalytics,
+        Action::GoBalances,
+        Action::GoWallet,
+        Action::Pay,
+        Action::SwitchAccount,
+        Action::AllTime,
+        Action::Month,
+        Action::Quarter,
+        Action::Year,
+        Action::PrevPeriod,
+        Action::NextPeriod,
+        Action::CustomRange,
+        Action::PeriodTransactions,
+        Action::Sort,
+        Action::SettleUp,
+        Action::CopyId,
+        Action::PayBack,
+        Action::RequestAgain,
+    ];
+
+    // actions that only mean something on one page, so their keys can be reused on another
+    pub const ANALYTICS: &'static [Action] = &[
+        Action::Month,
+        Action::Quarter,
+        Action::Year,
+        Action::PrevPeriod,
+        Action::NextPeriod,
+        Action::CustomRange,
+        Action::PeriodTransactions,
+    ];
+    pub const BALANCES: &'static [Action] = &[
+        Action::AllTime,
+        Action::Month,
+        Action::Quarter,
+        Action::Year,
+        Action::PrevPeriod,
+        Action::NextPeriod,
+        Action::Sort,
+        Action::SettleUp,
+    ];
+    // a transaction's detail pane
+    pub const DETAIL: &'static [Action] = &[Action::CopyId, Action::PayBack, Action::RequestAgain];
+    const PAGES: [&'static [Action]; 3] = [Action::ANALYTICS, Action::BALANCES, Action::DETAIL];
+
+    // the ones listed in the command palette, in order
+    pub const COMMANDS: [Action; 12] = [
+        Action::GoHome,
+        Action::GoTransactions,
+        Action::GoAnalytics,
+        Action::GoBalances,
+        Action::GoWallet,
+        Action::Pay,
+        Action::Search,
+        Action::Export,
+        Action::Refresh,
+        Action::SwitchAccount,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    // the name used in the config's [keys] table
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::NextItem => "next_item",
+            Action::PrevItem => "prev_item",
+            Action::FirstItem => "first_item",
+            Action::LastItem => "last_item",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Submit => "submit",
+            Action::Back => "back",
+            Action::Quit => "quit",
+            Action::Search => "search",
+            Action::Export => "export",
+            Action::Pin => "pin",
+            Action::Help => "help",
+            Action::Palette => "palette",
+            Action::Refresh => "refresh",
+            Action::GoHome => "go_home",
+            Action::GoTransactions => "go_transactions",
+            Action::GoAnalytics => "go_analytics",
+            Action::GoBalances => "go_balances",
+            Action::GoWallet => "go_wallet",
+            Action::Pay => "pay",
+            Action::SwitchAccount => "switch_account",
+            Action::AllTime => "all_time",
+            Action::Month => "month",
+            Action::Quarter => "quarter",
+            Action::Year => "year",
+            Action::PrevPeriod => "prev_period",
+            Action::NextPeriod => "next_period",
+            Action::CustomRange => "custom_range",
+            Action::PeriodTransactions => "period_transactions",
+            Action::Sort => "sort",
+            Action::SettleUp => "settle_up",
+            Action::CopyId => "copy_id",
+            Action::PayBack => "pay_back",
+            Action::RequestAgain => "request_again",
+        }
+    }
+
+    // shown in the help overlay and the command palette
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::NextItem => "Next item",
+            Action::PrevItem => "Previous item",
+            Action::FirstItem => "First item",
+            Action::LastItem => "Last item",
+            Action::PageDown => "Page down",
+            Action::PageUp => "Page up",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Submit => "Select",
+            Action::Back => "Back",
+            Action::Quit => "Quit",
+            Action::Search => "Search transactions",
+            Action::Export => "Export transactions",
+            Action::Pin => "Pin or unpin a favourite",
+            Action::Help => "Show key bindings",
+            Action::Palette => "Command palette",
+            Action::Refresh => "Refresh",
+            Action::GoHome => "Go to home",
+            Action::GoTransactions => "Go to transactions",
+            Action::GoAnalytics => "Go to analytics",
+            Action::GoBalances => "Go to balances",
+            Action::GoWallet => "Go to wallet",
+            Action::Pay => "Pay or request someone",
+            Action::SwitchAccount => "Switch account",
+            Action::AllTime => "All time",
+            Action::Month => "Month",
+            Action::Quarter => "Quarter",
+            Action::Year => "Year",
+            Action::PrevPeriod => "Previous period",
+            Action::NextPeriod => "Next period",
+            Action::CustomRange => "Custom range",
+            Action::PeriodTransactions => "The period's transactions",
+            Action::Sort => "Change the sort",
+            Action::SettleUp => "Settle up",
+            Action::CopyId => "Copy the transaction id",
+            Action::PayBack => "Pay the same amount back",
+            Action::RequestAgain => "Request the same amount again",
+        }
+    }
+
+    // handled by the home page whatever has focus
+    pub fn is_global(&self) -> bool {
+        matches!(
+            self,
+            Action::Quit
+                | Action::Help
+                | Action::Palette
+                | Action::Refresh
+                | Action::GoHome
+                | Action::GoTransactions
+                | Action::GoAnalytics
+                | Action::GoBalances
+                | Action::GoWallet
+                | Action::Pay
+                | Action::SwitchAccount
+        )
+    }
+
+    fn is_local(&self) -> bool {
+        Action::PAGES.iter().any(|page| page.contains(self))
+    }
+
+    // whether two actions can be bound to the same key: fine when they never share a page
+    fn clashes_with(&self, other: &Action) -> bool {
+        self != other
+            && (!self.is_local()
+                || !other.is_local()
+                || Action::PAGES
+                    .iter()
+                    .any(|page| page.contains(self) && page.contains(other)))
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|a| a.as_str() == name)
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// tui_textarea's Key can't be compared, so keep our own copy of the ones we bind
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Code {
+    Char(char),
+    F(u8),
+    Backspace,
+    Enter,
+    Left,
+    Right,
+    Up,
+    Down,
+    Tab,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Esc,
+}
+
+impl Code {
+    fn of(key: &Key) -> Option<Code> {
+        Some(match *key {
+            Key::Char(c) => Code::Char(c),
+            Key::F(n) => Code::F(n),
+            Key::Backspace => Code::Backspace,
+            Key::Enter => Code::Enter,
+            Key::Left => Code::Left,
+            Key::Right => Code::Right,
+            Key::Up => Code::Up,
+            Key::Down => Code::Down,
+            Key::Tab => Code::Tab,
+            Key::Delete => Code::Delete,
+            Key::Home => Code::Home,
+            Key::End => Code::End,
+            Key::PageUp => Code::PageUp,
+            Key::PageDown => Code::PageDown,
+            Key::Esc => Code::Esc,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Binding {
+    code: Code,
+    ctrl: bool,
+    alt: bool,
+}
+
+impl Binding {
+    // "j", "G", "ctrl-d", "alt-enter", "pagedown", "f1", "space"
+    pub fn parse(s: &str) -> Result<Binding, String> {
+        let mut rest = s;
+        let (mut ctrl, mut alt) = (false, false);
+        loop {
+            let lower = rest.to_lowercase();
+            if let Some(r) = ["ctrl-", "c-"]
+                .iter()
+                .find(|p| lower.starts_with(*p) && rest.len() > p.len())
+            {
+                ctrl = true;
+                rest = &rest[r.len()..];
+            } else if let Some(r) = ["alt-", "a-", "m-"]
+                .iter()
+                .find(|p| lower.starts_with(*p) && rest.len() > p.len())
+            {
+                alt = true;
+                rest = &rest[r.len()..];
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => Code::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "space" => Code::Char(' '),
+                "backspace" => Code::Backspace,
+                "enter" | "return" => Code::Enter,
+                "left" => Code::Left,
+                "right" => Code::Right,
+                "up" => Code::Up,
+                "down" => Code::Down,
+                "tab" => Code::Tab,
+                "delete" | "del" => Code::Delete,
+                "home" => Code::Home,
+                "end" => Code::End,
+                "pageup" | "pgup" => Code::PageUp,
+                "pagedown" | "pgdown" | "pgdn" => Code::PageDown,
+                "esc" | "escape" => Code::Esc,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n) if (1..=12).contains(&n) => Code::F(n),
+                    _ => return Err(format!("unknown key '{s}'")),
+                },
+            },
+        };
+
+        // ctrl-D and ctrl-d are the same key to the terminal
+        let code = match code {
+            Code::Char(c) if ctrl => Code::Char(c.to_ascii_lowercase()),
+            c => c,
+        };
+
+        Ok(Binding { code, ctrl, alt })
+    }
+
+    fn matches(&self, input: &Input) -> bool {
+        Code::of(&input.key) == Some(self.code) && input.ctrl == self.ctrl && input.alt == self.alt
+    }
+
+    // a key that would otherwise be typed into an input
+    fn is_text(&self) -> bool {
+        matches!(self.code, Code::Char(_)) && !self.ctrl && !self.alt
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            f.write_str("ctrl-")?;
+        }
+        if self.alt {
+            f.write_str("alt-")?;
+        }
+        match self.code {
+            Code::Char(' ') => f.write_str("space"),
+            Code::Char(c) => write!(f, "{c}"),
+            Code::F(n) => write!(f, "f{n}"),
+            Code::Backspace => f.write_str("backspace"),
+            Code::Enter => f.write_str("enter"),
+            Code::Left => f.write_str("left"),
+            Code::Right => f.write_str("right"),
+            Code::Up => f.write_str("up"),
+            Code::Down => f.write_str("down"),
+            Code::Tab => f.write_str("tab"),
+            Code::Delete => f.write_str("delete"),
+            Code::Home => f.write_str("home"),
+            Code::End => f.write_str("end"),
+            Code::PageUp => f.write_str("pageup"),
+            Code::PageDown => f.write_str("pagedown"),
+            Code::Esc => f.write_str("esc"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: Vec<(Binding, Action)>,
+}
+
+impl Keymap {
+    fn from_keys(keys: &[(Action, &[&str])]) -> Self {
+        let bindings = keys
+            .iter()
+            .flat_map(|(action, keys)| {
+                keys.iter().map(|k| {
+                    (
+                        Binding::parse(k).expect("failed to parse built-in key"),
+                        *action,
+                    )
+                })
+            })
+            .collect();
+        Keymap { bindings }
+    }
+
+    pub fn default_keys() -> Self {
+        Keymap::from_keys(&[
+            (Action::NextItem, &["down"]),
+            (Action::PrevItem, &["up"]),
+            (Action::FirstItem, &["home"]),
+            (Action::LastItem, &["end"]),
+            (Action::PageDown, &["pagedown"]),
+            (Action::PageUp, &["pageup"]),
+            (Action::Left, &["left"]),
+            (Action::Right, &["right"]),
+            (Action::Submit, &["enter"]),
+            (Action::Back, &["esc"]),
+            (Action::Quit, &["ctrl-c", "ctrl-q"]),
+            (Action::Search, &["/"]),
+            (Action::Export, &["e"]),
+            (Action::Pin, &["ctrl-f"]),
+            (Action::Help, &["?"]),
+            (Action::Palette, &[":", "ctrl-p"]),
+            (Action::Refresh, &["ctrl-r"]),
+            (Action::AllTime, &["a"]),
+            (Action::Month, &["m"]),
+            (Action::Quarter, &["q"]),
+            (Action::Year, &["y"]),
+            (Action::PrevPeriod, &["["]),
+            (Action::NextPeriod, &["]"]),
+            (Action::CustomRange, &["c"]),
+            (Action::PeriodTransactions, &["d"]),
+            (Action::Sort, &["o"]),
+            (Action::SettleUp, &["s"]),
+            (Action::CopyId, &["c"]),
+            (Action::PayBack, &["p"]),
+            (Action::RequestAgain, &["r"]),
+        ])
+    }
+
+    // the default keys plus hjkl, g/G and ctrl-d/u
+    pub fn vim() -> Self {
+        let mut keymap = Keymap::default_keys();
+        keymap.bindings.extend(
+            Keymap::from_keys(&[
+                (Action::NextItem, &["j"]),
+                (Action::PrevItem, &["k"]),
+                (Action::FirstItem, &["g"]),
+                (Action::LastItem, &["G"]),
+                (Action::PageDown, &["ctrl-d"]),
+                (Action::PageUp, &["ctrl-u"]),
+                (Action::Left, &["h"]),
+                (Action::Right, &["l"]),
+            ])
+            .bindings,
+        );
+        keymap
+    }
+
+    // a preset with the config's [keys] overrides applied. each override replaces all of the
+    // preset's keys for that action
+    pub fn resolve(preset: &str, overrides: &HashMap<String, Vec<String>>) -> Result<Self, String> {
+        let mut keymap = match preset {
+            "default" => Keymap::default_keys(),
+            "vim" => Keymap::vim(),
+            _ => {
+                return Err(format!(
+                    "unknown keymap '{preset}' (use {})",
+                    PRESETS.join(", ")
+                ))
+            }
+        };
+
+        for (name, keys) in overrides.iter() {
+            let action = Action::from_name(name).ok_or_else(|| {
+                format!(
+                    "unknown action '{name}' in [keys] (use {})",
+                    Action::ALL.map(|a| a.as_str()).join(", ")
+                )
+            })?;
+            keymap.bindings.retain(|(_, a)| *a != action);
+            for key in keys {
+                let binding = Binding::parse(key).map_err(|e| format!("[keys] {name}: {e}"))?;
+                keymap.bindings.push((binding, action));
+            }
+        }
+
+        for (i, (binding, action)) in keymap.bindings.iter().enumerate() {
+            if let Some((_, other)) = keymap.bindings[..i]
+                .iter()
+                .find(|(b, a)| b == binding && a.clashes_with(action))
+            {
+                return Err(format!(
+                    "key '{binding}' is bound to both {other} and {action}"
+                ));
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    // the action bound to a key, leaving out the pages' own (see on_page). while typing into an
+    // input, plain characters are left alone
+    pub fn action(&self, input: &Input, typing: bool) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(b, a)| !a.is_local() && b.matches(input) && !(typing && b.is_text()))
+            .map(|(_, a)| *a)
+    }
+
+    // which of a page's own actions a key is bound to, e.g. on_page(&input, Action::ANALYTICS)
+    pub fn on_page(&self, input: &Input, page: &[Action]) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(b, a)| page.contains(a) && b.matches(input))
+            .map(|(_, a)| *a)
+    }
+
+    pub fn keys(&self, action: Action) -> impl Iterator<Item = &Binding> {
+        self.bindings
+            .iter()
+            .filter(move |(_, a)| *a == action)
+            .map(|(b, _)| b)
+    }
+
+    // e.g. "down/j", or "" when unbound
+    pub fn describe(&self, action: Action) -> String {
+        self.keys(action)
+            .map(|b| b.to_string())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+pub fn get() -> &'static Keymap {
+    KEYMAP.get_or_init(|| {
+        let config = config::get();
+        // the config was validated at startup
+        Keymap::resolve(&config.keymap, &config.keys).unwrap_or_else(|_| Keymap::default_keys())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> Input {
+        Input {
+            key: Key::Char(c),
+            ctrl: false,
+            alt: false,
+        }
+    }
+
+    fn overrides(keys: &[(&str, &str)]) -> HashMap<String, Vec<String>> {
+        keys.iter()
+            .map(|(action, key)| (action.to_string(), vec![key.to_string()]))
+            .collect()
+    }
+
+    #[test]
+    fn page_keys_can_be_reused_on_other_pages() {
+        let keymap = Keymap::resolve("vim", &HashMap::new()).unwrap();
+        assert_eq!(keymap.action(&key('c'), false), None);
+        assert_eq!(
+            keymap.on_page(&key('c'), Action::ANALYTICS),
+            Some(Action::CustomRange)
+        );
+        assert_eq!(
+            keymap.on_page(&key('c'), Action::DETAIL),
+            Some(Action::CopyId)
+        );
+    }
+
+    #[test]
+    fn page_keys_clash_on_their_own_page_and_with_global_ones() {
+        let same_page = overrides(&[("month", "y")]);
+        assert!(Keymap::resolve("default", &same_page).is_err());
+        let global = overrides(&[("copy_id", "e")]);
+        assert!(Keymap::resolve("default", &global).is_err());
+        let other_page = overrides(&[("copy_id", "m")]);
+        assert!(Keymap::resolve("default", &other_page).is_ok());
+    }
+}
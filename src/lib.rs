@@ -1,3 +1,15 @@
 pub mod api;
+pub mod cache;
+pub mod cli;
+pub mod config;
+pub mod crash;
+pub mod daemon;
+pub mod export;
+pub mod hooks;
+pub mod journal;
+pub mod keymap;
 pub mod pages;
-pub mod types;
\ No newline at end of file
+pub mod search;
+pub mod stats;
+pub mod theme;
+pub mod types;
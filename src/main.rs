@@ -1,59 +1,278 @@
-use core::panic;
-use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
-use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
-};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use crossterm::event::EnableMouseCapture;
+use crossterm::terminal::{enable_raw_mode, EnterAlternateScreen};
 use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
 use tui::backend::CrosstermBackend;
 use tui::Terminal;
-use venmo_tui::api::Api;
-use venmo_tui::pages::home::draw_home_page;
+use venmo_tui::api::PaymentType;
+use venmo_tui::cli;
+use venmo_tui::config::{self, Config};
+use venmo_tui::crash;
+use venmo_tui::daemon::{self, PaymentParams};
+use venmo_tui::export::Format;
+use venmo_tui::journal::JournalFormat;
+use venmo_tui::pages::error::{show_error, Response};
+use venmo_tui::pages::home::{draw_home_page, Exit};
 use venmo_tui::pages::login::draw_login_page;
+use venmo_tui::types::StorySubType;
+
+#[derive(Parser)]
+#[command(version, about = "a little terminal UI for venmo")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Print machine-readable json (one value per line) instead of text
+    #[arg(long, global = true)]
+    json: bool,
+    /// Config file, instead of $XDG_CONFIG_HOME/venmo-tui/config.toml
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Export transaction history as csv, json (lines), ofx or qif
+    Export {
+        #[arg(short, long, default_value = "csv")]
+        format: Format,
+        /// Only include stories on or after this day (YYYY-MM-DD)
+        #[arg(long)]
+        after: Option<NaiveDate>,
+        /// Only include stories before this day (YYYY-MM-DD)
+        #[arg(long)]
+        before: Option<NaiveDate>,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Append new transactions to a ledger, hledger or beancount journal
+    Journal {
+        /// The journal to append to (created if missing)
+        file: PathBuf,
+        #[arg(short, long, default_value = "ledger")]
+        format: JournalFormat,
+        /// TOML file with account names and counterparty-to-account rules
+        #[arg(short, long)]
+        rules: Option<PathBuf>,
+        /// Only include stories on or after this day (YYYY-MM-DD)
+        #[arg(long)]
+        after: Option<NaiveDate>,
+    },
+    /// Print the current venmo balance
+    Balance,
+    /// Print transaction history, newest first
+    History {
+        /// Only include stories on or after this day (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// Only include stories before this day (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<NaiveDate>,
+    },
+    /// Pay someone, e.g. `pay @user 12.50 "pizza"`
+    Pay {
+        handle: String,
+        #[arg(value_parser = cli::parse_amount)]
+        amount: u32,
+        #[arg(default_value = "")]
+        note: String,
+        /// Funding instrument id to pay with (see `instruments`), instead of the
+        /// default picked on the wallet page or default_funding_source from the config
+        #[arg(long)]
+        source: Option<String>,
+        /// Send without asking for confirmation first
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Request money from someone, e.g. `request @user 12.50 "pizza"`
+    Request {
+        handle: String,
+        #[arg(value_parser = cli::parse_amount)]
+        amount: u32,
+        #[arg(default_value = "")]
+        note: String,
+        /// Send without asking for confirmation first
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Search for people by name or handle
+    Search { name: String },
+    /// List funding instruments (bank accounts, cards, balance)
+    Instruments,
+    /// Print new transactions as JSON lines as they arrive
+    Tail {
+        /// Also print stories since this day (YYYY-MM-DD) before waiting for new ones
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// Only print stories of this type (p2p, standardTransfer, creditReward,
+        /// creditRepayment); repeatable
+        #[arg(long = "type")]
+        types: Vec<StorySubType>,
+        /// Seconds between polls when no daemon is running, instead of poll_interval
+        /// from the config
+        #[arg(long)]
+        interval: Option<u64>,
+    },
+    /// Keep a session alive, serve JSON-RPC on a unix socket and run the config's hooks
+    Daemon {
+        /// Where to listen, instead of $XDG_RUNTIME_DIR/venmo-tui.sock. the
+        /// directory must be one only you can access
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+}
+
+async fn run_command(command: Command, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if let Command::Daemon { socket } = command {
+        let api = match cli::session().await? {
+            cli::Session::Local(api) => api,
+            cli::Session::Daemon(_) => return Err("a daemon is already running".into()),
+        };
+        return daemon::serve(api, &socket.unwrap_or_else(daemon::socket_path)).await;
+    }
+
+    let mut session = cli::session().await?;
+
+    match command {
+        Command::Export {
+            format,
+            after,
+            before,
+            output,
+        } => cli::export_history(&mut session, format, after, before, output).await,
+        Command::Journal {
+            file,
+            format,
+            rules,
+            after,
+        } => cli::sync_journal(&mut session, file, format, rules, after).await,
+        Command::Balance => cli::balance(&mut session, json).await,
+        Command::History { since, until } => {
+            cli::print_history(&mut session, since, until, json).await
+        }
+        Command::Pay {
+            handle,
+            amount,
+            note,
+            source,
+            yes,
+        } => {
+            let payment = PaymentParams {
+                handle,
+                user_id: None,
+                amount_cents: amount,
+                note,
+                source,
+            };
+            cli::send_payment(&mut session, PaymentType::Pay, payment, yes, json).await
+        }
+        Command::Request {
+            handle,
+            amount,
+            note,
+            yes,
+        } => {
+            let payment = PaymentParams {
+                handle,
+                user_id: None,
+                amount_cents: amount,
+                note,
+                source: None,
+            };
+            cli::send_payment(&mut session, PaymentType::Request, payment, yes, json).await
+        }
+        Command::Search { name } => cli::search_people(&mut session, &name, json).await,
+        Command::Instruments => cli::instruments(&mut session, json).await,
+        Command::Tail {
+            since,
+            types,
+            interval,
+        } => {
+            let interval = interval.unwrap_or(config::get().poll_interval);
+            cli::tail(&mut session, Duration::from_secs(interval), since, types).await
+        }
+        Command::Daemon { .. } => unreachable!(),
+    }
+}
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
-    let stdout = io::stdout();
-    let mut stdout = stdout.lock();
+    let args = Cli::parse();
 
-    let mut api = Box::leak(Box::new(match Api::new().await {
+    match Config::load(args.config.as_deref()) {
+        Ok(c) => config::init(c),
         Err(e) => {
-            panic!("{e}");
+            eprintln!("{e}");
+            std::process::exit(1);
         }
-        Ok(v) => v,
-    }));
+    }
+
+    if let Some(command) = args.command {
+        if let Err(e) = run_command(command, args.json).await {
+            eprintln!("{e}");
+            std::process::exit(cli::exit_code(e.as_ref()));
+        }
+        return Ok(());
+    }
+
+    // before anything that could panic, so every crash gets a report
+    crash::install_panic_hook();
+    crash::handle_signals();
+
+    // whichever way the tui ends, leave the terminal usable
+    let result = tui().await;
+    crash::restore_terminal()?;
+    result
+}
 
-    let logged_in = api.logged_in().await;
+async fn tui() -> io::Result<()> {
+    let mut stdout = io::stdout();
 
     enable_raw_mode()?;
-    crossterm::execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    crossterm::execute!(stdout, EnterAlternateScreen)?;
+    if config::get().mouse {
+        crossterm::execute!(stdout, EnableMouseCapture)?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut term = Terminal::new(backend)?;
 
-    if !logged_in {
-        if let None = draw_login_page(&mut term, &mut api).await? {
-            disable_raw_mode()?;
-            crossterm::execute!(
-                term.backend_mut(),
-                LeaveAlternateScreen,
-                DisableMouseCapture
-            )?;
-            term.show_cursor()?;
-            return Ok(());
+    // nothing works without an api, so either it's tried again or we quit
+    let api = loop {
+        match cli::tui_api().await {
+            Ok(api) => break Box::leak(Box::new(api)),
+            Err(e) => match show_error(&mut term, e.to_string(), true).await? {
+                Response::Retry => {}
+                Response::Dismiss => return Ok(()),
+            },
         }
-    }
+    };
 
-    // load identity before drawing home page
-    api.get_profile().await.expect("failed to get profile");
+    let mut logged_in = api.offline || api.logged_in().await;
 
-    draw_home_page(&mut term, api).await?;
+    'outer: loop {
+        if !logged_in && draw_login_page(&mut term, api).await?.is_none() {
+            break;
+        }
 
-    disable_raw_mode()?;
-    crossterm::execute!(
-        term.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    term.show_cursor()?;
+        // load identity before drawing home page (offline, it came from the cache)
+        while !api.offline {
+            match api.get_profile().await {
+                Ok(_) => break,
+                Err(e) => match show_error(&mut term, e.to_string(), true).await? {
+                    Response::Retry => {}
+                    Response::Dismiss => break 'outer,
+                },
+            }
+        }
+
+        match draw_home_page(&mut term, api).await? {
+            Exit::Quit => break,
+            Exit::SwitchAccount => logged_in = false,
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,397 @@
+use std::{io::Stdout, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{Local, NaiveDate};
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{BarChart, Block, Borders, Clear, List, ListItem, Paragraph, Sparkline},
+    Frame,
+};
+use tui_textarea::{Input, TextArea};
+
+use crate::{
+    api::{Api, ApiError},
+    config,
+    keymap::{self, Action, PAGE_STEP},
+    stats::{covers, CounterpartyTotals, Period, PeriodKind, Summary},
+    theme,
+    types::{format_dollars, Story},
+};
+
+use super::{activate, bound, centered_rect, home::StatefulList, Navigate, Page};
+
+pub struct AnalyticsPage<'a> {
+    api: &'a mut Api,
+    syncing: bool,
+    // paging back until the cache reaches the start of the period
+    loading_older: bool,
+    editing_range: bool,
+    stories: Vec<Story>,
+    period: Period,
+    summary: Summary,
+    counterparties: StatefulList<CounterpartyTotals>,
+    range: TextArea<'a>,
+    navigate: Option<Navigate>,
+}
+
+fn range_input<'a>() -> TextArea<'a> {
+    let mut range = TextArea::default();
+    range.set_block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Range (YYYY-MM-DD..YYYY-MM-DD)"),
+    );
+    activate(&mut range);
+    range
+}
+
+impl<'a> AnalyticsPage<'a> {
+    pub fn new(api: &'a mut Api) -> Self {
+        let mut page = Self {
+            syncing: !api.offline,
+            loading_older: false,
+            editing_range: false,
+            stories: api.cache.stories(),
+            api,
+            period: Period::containing(PeriodKind::Month, Local::now().date_naive()),
+            summary: Summary::default(),
+            counterparties: StatefulList::with_items(vec![]),
+            range: range_input(),
+            navigate: None,
+        };
+        page.summarize();
+        page
+    }
+
+    fn summarize(&mut self) {
+        self.summary = Summary::new(&self.stories, &self.period);
+        self.counterparties = StatefulList::with_items(self.summary.counterparties.clone());
+    }
+
+    fn set_period(&mut self, period: Period) {
+        self.period = period;
+        self.loading_older = !self.api.offline && !self.covered();
+        self.summarize();
+    }
+
+    // whether the cache goes back far enough for the period's totals to be complete
+    fn covered(&self) -> bool {
+        covers(
+            &self.stories,
+            self.api.cache.reached_end(),
+            Some(self.period.start),
+        )
+    }
+
+    fn status(&self) -> String {
+        if self.syncing {
+            return "   (syncing...)".to_string();
+        }
+        if self.loading_older {
+            return "   (loading older transactions...)".to_string();
+        }
+        if self.covered() {
+            return String::new();
+        }
+        match self.stories.last().and_then(|s| s.day()) {
+            Some(day) => format!("   (partial: only cached back to {day})"),
+            None => "   (partial: nothing cached)".to_string(),
+        }
+    }
+
+    fn submit_range(&mut self) {
+        let text = self.range.lines()[0].clone();
+        let parsed = text.split_once("..").and_then(|(start, end)| {
+            Some((
+                NaiveDate::parse_from_str(start.trim(), "%Y-%m-%d").ok()?,
+                NaiveDate::parse_from_str(end.trim(), "%Y-%m-%d").ok()?,
+            ))
+        });
+
+        match parsed {
+            Some((start, end)) if start <= end => {
+                self.editing_range = false;
+                self.range = range_input();
+                self.set_period(Period::custom(start, end));
+            }
+            _ => {
+                self.range
+                    .set_style(Style::default().fg(theme::get().error));
+                self.range.set_block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("ERROR: use YYYY-MM-DD..YYYY-MM-DD"),
+                );
+            }
+        }
+    }
+
+    fn render_bars(
+        &self,
+        f: &mut Frame<CrosstermBackend<Stdout>>,
+        area: Rect,
+        title: &str,
+        color: Color,
+        value: impl Fn(&crate::stats::MonthTotals) -> i64,
+    ) {
+        let labels = self
+            .summary
+            .months
+            .iter()
+            .map(|m| m.month.format("%b").to_string())
+            .collect::<Vec<_>>();
+        let data = self
+            .summary
+            .months
+            .iter()
+            .zip(labels.iter())
+            .map(|(m, l)| (l.as_str(), (value(m) / 100) as u64))
+            .collect::<Vec<_>>();
+
+        let bar_width = (area.width.saturating_sub(2) / data.len().max(1) as u16)
+            .saturating_sub(1)
+            .clamp(1, 9);
+
+        let chart = BarChart::default()
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .data(&data)
+            .bar_width(bar_width)
+            .bar_style(Style::default().fg(color))
+            .value_style(Style::default().fg(theme::get().surface).bg(color));
+        f.render_widget(chart, area);
+    }
+}
+
+#[async_trait]
+impl<'a> Page for AnalyticsPage<'a> {
+    async fn on_input_event(&mut self, event: Input) -> bool {
+        let action = keymap::get().action(&event, self.typing());
+
+        if self.editing_range {
+            match action {
+                Some(Action::Submit) => self.submit_range(),
+                Some(Action::Back) => {
+                    self.editing_range = false;
+                    self.range = range_input();
+                }
+                _ => {
+                    self.range.input(event);
+                }
+            }
+            return false;
+        }
+
+        let people = &mut self.counterparties;
+        let any = !people.items.is_empty();
+        match action {
+            Some(Action::NextItem) if any => people.next(),
+            Some(Action::PrevItem) if any => people.previous(),
+            Some(Action::FirstItem) if any => people.first(),
+            Some(Action::LastItem) if any => people.last(),
+            Some(Action::PageDown) if any => people.forward(PAGE_STEP),
+            Some(Action::PageUp) if any => people.back(PAGE_STEP),
+            // drill into the period's stories with the selected person
+            Some(Action::Submit) => {
+                if let Some(i) = self.counterparties.state.selected() {
+                    if let Some(person) = self.counterparties.items.get(i) {
+                        self.navigate = Some(Navigate::Transactions(format!(
+                            "with:@{} {}",
+                            person.username,
+                            self.period.query()
+                        )));
+                    }
+                }
+            }
+            Some(Action::Left) | Some(Action::Back) => return true,
+            _ => match keymap::get().on_page(&event, Action::ANALYTICS) {
+                Some(Action::Month) => {
+                    self.set_period(Period::containing(PeriodKind::Month, self.period.start))
+                }
+                Some(Action::Quarter) => {
+                    self.set_period(Period::containing(PeriodKind::Quarter, self.period.start))
+                }
+                Some(Action::Year) => {
+                    self.set_period(Period::containing(PeriodKind::Year, self.period.start))
+                }
+                Some(Action::CustomRange) => self.editing_range = true,
+                Some(Action::PrevPeriod) => self.set_period(self.period.previous()),
+                Some(Action::NextPeriod) => self.set_period(self.period.next()),
+                // or into all of the period's stories
+                Some(Action::PeriodTransactions) => {
+                    self.navigate = Some(Navigate::Transactions(self.period.query()))
+                }
+                _ => {}
+            },
+        }
+        false
+    }
+
+    fn typing(&self) -> bool {
+        self.editing_range
+    }
+
+    fn help(&self) -> Vec<(String, &'static str)> {
+        if self.editing_range {
+            return vec![
+                bound(Action::Submit, "Use the range"),
+                bound(Action::Back, "Cancel"),
+            ];
+        }
+        vec![
+            bound(Action::Month, "Month"),
+            bound(Action::Quarter, "Quarter"),
+            bound(Action::Year, "Year"),
+            bound(Action::PrevPeriod, "Previous period"),
+            bound(Action::NextPeriod, "Next period"),
+            bound(Action::CustomRange, "Custom range"),
+            bound(Action::NextItem, "Next person"),
+            bound(Action::PrevItem, "Previous person"),
+            bound(Action::Submit, "Transactions with the person"),
+            bound(
+                Action::PeriodTransactions,
+                "All of the period's transactions",
+            ),
+            bound(Action::Back, "Back to the sidebar"),
+        ]
+    }
+
+    async fn make_progress(&mut self) -> Result<bool, ApiError> {
+        if self.syncing {
+            self.api.sync_stories(config::get().page_size).await?;
+            self.stories = self.api.cache.stories();
+            self.syncing = false;
+            self.loading_older = !self.covered();
+            self.summarize();
+            return Ok(true);
+        }
+        // a page at a time, letting input through in between (see wake_in) so the
+        // totals fill in as they load and the period can still be changed
+        if self.loading_older {
+            self.api.load_older_stories(config::get().page_size).await?;
+            self.stories = self.api.cache.stories();
+            self.loading_older = !self.covered();
+            self.summarize();
+        }
+        Ok(false)
+    }
+
+    fn wake_in(&self) -> Option<Duration> {
+        self.loading_older.then_some(Duration::ZERO)
+    }
+
+    // keep showing what's cached
+    fn cancel(&mut self) {
+        self.syncing = false;
+        self.loading_older = false;
+    }
+
+    fn navigate(&mut self) -> Option<Navigate> {
+        self.navigate.take()
+    }
+
+    fn render(&mut self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Length(4),
+                    Constraint::Length(12),
+                    Constraint::Min(6),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        let summary = Paragraph::new(vec![
+            Spans::from(format!(
+                "In {}   Out {}   Net {}   {} payments, {} on average{}",
+                format_dollars(self.summary.in_cents),
+                format_dollars(self.summary.out_cents),
+                format_dollars(self.summary.net_cents()),
+                self.summary.count,
+                format_dollars(self.summary.average_cents()),
+                self.status()
+            )),
+            Spans::from(
+                "[m]onth [q]uarter [y]ear [c]ustom   [ ] previous/next   [d] transactions   enter: transactions with person",
+            ),
+        ])
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(Span::styled(
+                    self.period.to_string(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                )),
+        );
+        f.render_widget(summary, chunks[0]);
+
+        let bars = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(chunks[1]);
+        self.render_bars(f, bars[0], "In by month ($)", theme::get().positive, |m| {
+            m.in_cents
+        });
+        self.render_bars(f, bars[1], "Out by month ($)", theme::get().negative, |m| {
+            m.out_cents
+        });
+
+        let bottom = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(chunks[2]);
+
+        let people = self
+            .counterparties
+            .items
+            .iter()
+            .map(|p| {
+                ListItem::new(Spans::from(format!(
+                    "{} (@{})  in {}  out {}  x{}",
+                    p.display_name,
+                    p.username,
+                    format_dollars(p.in_cents),
+                    format_dollars(p.out_cents),
+                    p.count
+                )))
+            })
+            .collect::<Vec<_>>();
+        let people = List::new(people)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Top counterparties"),
+            )
+            .highlight_style(theme::get().highlight());
+        f.render_stateful_widget(people, bottom[0], &mut self.counterparties.state);
+
+        // sparklines can't go negative, so shift everything up by the low point
+        let low = self.summary.running_net.iter().min().copied().unwrap_or(0);
+        let high = self.summary.running_net.iter().max().copied().unwrap_or(0);
+        let net = self
+            .summary
+            .running_net
+            .iter()
+            .map(|v| (v - low) as u64)
+            .collect::<Vec<_>>();
+        let sparkline = Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(format!(
+                "Running net (low {}, high {})",
+                format_dollars(low),
+                format_dollars(high)
+            )))
+            .data(&net)
+            .style(Style::default().fg(theme::get().accent));
+        f.render_widget(sparkline, bottom[1]);
+
+        if self.editing_range {
+            let popup = centered_rect(50, 20, area);
+            f.render_widget(Clear, popup);
+            f.render_widget(self.range.widget(), popup);
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use std::{io::Stdout, time::Duration};
+
+use async_trait::async_trait;
+use chrono::Local;
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+    Frame,
+};
+use tui_textarea::Input;
+
+use crate::{
+    api::{Api, ApiError, PaymentType},
+    config,
+    keymap::{self, Action, PAGE_STEP},
+    stats::{counterparties, covers, payments, CounterpartyTotals, Period, PeriodKind},
+    theme,
+    types::{format_dollars, Story},
+};
+
+use super::{bound, home::StatefulList, Navigate, Page};
+
+#[derive(Copy, Clone, PartialEq)]
+enum SortBy {
+    Net,
+    Name,
+    Volume,
+}
+
+impl SortBy {
+    fn next(self) -> Self {
+        match self {
+            SortBy::Net => SortBy::Name,
+            SortBy::Name => SortBy::Volume,
+            SortBy::Volume => SortBy::Net,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortBy::Net => "amount owed",
+            SortBy::Name => "name",
+            SortBy::Volume => "volume",
+        }
+    }
+}
+
+// who owes whom, from p2p stories: what they've paid us minus what we've paid them
+pub struct BalancesPage<'a> {
+    api: &'a mut Api,
+    syncing: bool,
+    // paging back until the window is covered, see covered
+    loading_older: bool,
+    stories: Vec<Story>,
+    // none means all time
+    window: Option<Period>,
+    sort_by: SortBy,
+    people: StatefulList<CounterpartyTotals>,
+    navigate: Option<Navigate>,
+    notice: Option<String>,
+}
+
+fn describe(net: i64) -> (String, Color) {
+    match net {
+        n if n > 0 => (
+            format!("you owe {}", format_dollars(n)),
+            theme::get().negative,
+        ),
+        n if n < 0 => (
+            format!("owes you {}", format_dollars(-n)),
+            theme::get().positive,
+        ),
+        _ => ("settled up".to_string(), theme::get().muted),
+    }
+}
+
+impl<'a> BalancesPage<'a> {
+    pub fn new(api: &'a mut Api) -> Self {
+        let mut page = Self {
+            syncing: !api.offline,
+            loading_older: false,
+            stories: api.cache.stories(),
+            api,
+            window: None,
+            sort_by: SortBy::Net,
+            people: StatefulList::with_items(vec![]),
+            navigate: None,
+            notice: None,
+        };
+        page.tally();
+        page
+    }
+
+    fn tally(&mut self) {
+        let mut people = counterparties(&self.stories, self.window.as_ref());
+        match self.sort_by {
+            SortBy::Net => people.sort_by_key(|p| -p.net().abs()),
+            SortBy::Name => people.sort_by_key(|p| p.display_name.to_lowercase()),
+            SortBy::Volume => people.sort_by_key(|p| -p.volume()),
+        }
+
+        // keep the same person selected across re-sorts
+        let selected = self.selected().map(|p| p.username.clone());
+        self.people = StatefulList::with_items(people);
+        if let Some(i) =
+            selected.and_then(|u| self.people.items.iter().position(|p| p.username == u))
+        {
+            self.people.state.select(Some(i));
+        }
+    }
+
+    fn selected(&self) -> Option<&CounterpartyTotals> {
+        self.people
+            .state
+            .selected()
+            .and_then(|i| self.people.items.get(i))
+    }
+
+    fn set_window(&mut self, window: Option<Period>) {
+        self.window = window;
+        self.loading_older = !self.api.offline && !self.covered();
+        self.tally();
+    }
+
+    // whether the cache goes back far enough for the window's balances to be complete
+    fn covered(&self) -> bool {
+        covers(
+            &self.stories,
+            self.api.cache.reached_end(),
+            self.window.map(|w| w.start),
+        )
+    }
+
+    fn status(&self) -> String {
+        if self.syncing {
+            return "   (syncing...)".to_string();
+        }
+        if self.loading_older {
+            return "   (loading older transactions...)".to_string();
+        }
+        if self.covered() {
+            return String::new();
+        }
+        match self.stories.last().and_then(|s| s.day()) {
+            Some(day) => format!("   (partial: only cached back to {day})"),
+            None => "   (partial: nothing cached)".to_string(),
+        }
+    }
+
+    fn window_start(&self) -> chrono::NaiveDate {
+        self.window
+            .map(|w| w.start)
+            .unwrap_or_else(|| Local::now().date_naive())
+    }
+
+    fn settle_up(&mut self) {
+        let Some(person) = self.selected() else {
+            return;
+        };
+
+        let net = person.net();
+        if net == 0 {
+            return;
+        }
+        // an amount from some of the history would settle the wrong figure
+        if !self.covered() {
+            self.notice = Some(
+                "can't settle up from partial balances, wait for older transactions to load"
+                    .to_string(),
+            );
+            return;
+        }
+
+        self.navigate = Some(Navigate::Pay {
+            handle: person.username.clone(),
+            amount_cents: net.abs(),
+            note: "settling up".to_string(),
+            payment_type: if net > 0 {
+                PaymentType::Pay
+            } else {
+                PaymentType::Request
+            },
+        });
+    }
+}
+
+#[async_trait]
+impl<'a> Page for BalancesPage<'a> {
+    async fn on_input_event(&mut self, event: Input) -> bool {
+        let people = &mut self.people;
+        let any = !people.items.is_empty();
+        match keymap::get().action(&event, false) {
+            Some(Action::NextItem) if any => people.next(),
+            Some(Action::PrevItem) if any => people.previous(),
+            Some(Action::FirstItem) if any => people.first(),
+            Some(Action::LastItem) if any => people.last(),
+            Some(Action::PageDown) if any => people.forward(PAGE_STEP),
+            Some(Action::PageUp) if any => people.back(PAGE_STEP),
+            Some(Action::Left) | Some(Action::Back) => return true,
+            _ => match keymap::get().on_page(&event, Action::BALANCES) {
+                Some(Action::AllTime) => self.set_window(None),
+                Some(Action::Month) => self.set_window(Some(Period::containing(
+                    PeriodKind::Month,
+                    self.window_start(),
+                ))),
+                Some(Action::Quarter) => self.set_window(Some(Period::containing(
+                    PeriodKind::Quarter,
+                    self.window_start(),
+                ))),
+                Some(Action::Year) => self.set_window(Some(Period::containing(
+                    PeriodKind::Year,
+                    self.window_start(),
+                ))),
+                Some(Action::PrevPeriod) => self.set_window(self.window.map(|w| w.previous())),
+                Some(Action::NextPeriod) => self.set_window(self.window.map(|w| w.next())),
+                Some(Action::Sort) => {
+                    self.sort_by = self.sort_by.next();
+                    self.tally();
+                }
+                Some(Action::SettleUp) => self.settle_up(),
+                _ => {}
+            },
+        }
+        false
+    }
+
+    async fn make_progress(&mut self) -> Result<bool, ApiError> {
+        if self.syncing {
+            self.api.sync_stories(config::get().page_size).await?;
+            self.stories = self.api.cache.stories();
+            self.syncing = false;
+            self.loading_older = !self.covered();
+            self.tally();
+            return Ok(true);
+        }
+        // a page at a time, like the analytics page, so input still gets through
+        if self.loading_older {
+            self.api.load_older_stories(config::get().page_size).await?;
+            self.stories = self.api.cache.stories();
+            self.loading_older = !self.covered();
+            self.tally();
+        }
+        Ok(false)
+    }
+
+    fn wake_in(&self) -> Option<Duration> {
+        self.loading_older.then_some(Duration::ZERO)
+    }
+
+    // keep showing what's cached
+    fn cancel(&mut self) {
+        self.syncing = false;
+        self.loading_older = false;
+    }
+
+    fn notice(&mut self) -> Option<String> {
+        self.notice.take()
+    }
+
+    fn help(&self) -> Vec<(String, &'static str)> {
+        vec![
+            bound(Action::AllTime, "All time"),
+            bound(Action::Month, "Month"),
+            bound(Action::Quarter, "Quarter"),
+            bound(Action::Year, "Year"),
+            bound(Action::PrevPeriod, "Previous period"),
+            bound(Action::NextPeriod, "Next period"),
+            bound(Action::Sort, "Change the sort"),
+            bound(Action::NextItem, "Next person"),
+            bound(Action::PrevItem, "Previous person"),
+            bound(Action::SettleUp, "Settle up with the person"),
+            bound(Action::Back, "Back to the sidebar"),
+        ]
+    }
+
+    fn navigate(&mut self) -> Option<Navigate> {
+        self.navigate.take()
+    }
+
+    fn render(&mut self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(4), Constraint::Min(5)].as_ref())
+            .split(area);
+
+        let owed = self
+            .people
+            .items
+            .iter()
+            .filter(|p| p.net() < 0)
+            .map(|p| -p.net())
+            .sum::<i64>();
+        let owing = self
+            .people
+            .items
+            .iter()
+            .filter(|p| p.net() > 0)
+            .map(|p| p.net())
+            .sum::<i64>();
+
+        let header = Paragraph::new(vec![
+            Spans::from(format!(
+                "You're owed {} and owe {}{}",
+                format_dollars(owed),
+                format_dollars(owing),
+                self.status()
+            )),
+            Spans::from(
+                "[a]ll time [m]onth [q]uarter [y]ear   [ ] previous/next   [o] sort   [s] settle up",
+            ),
+        ])
+        .block(Block::default().borders(Borders::ALL).title(Span::styled(
+            format!(
+                "Balances, {} (by {})",
+                self.window
+                    .map(|w| w.to_string())
+                    .unwrap_or_else(|| "all time".to_string()),
+                self.sort_by.label()
+            ),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        f.render_widget(header, chunks[0]);
+
+        let body = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)].as_ref())
+            .split(chunks[1]);
+
+        let people = self
+            .people
+            .items
+            .iter()
+            .map(|p| {
+                let (text, color) = describe(p.net());
+                ListItem::new(Spans::from(vec![
+                    Span::raw(format!("{} (@{})  ", p.display_name, p.username)),
+                    Span::styled(text, Style::default().fg(color)),
+                ]))
+            })
+            .collect::<Vec<_>>();
+        let people = List::new(people)
+            .block(Block::default().borders(Borders::ALL).title("People"))
+            .highlight_style(theme::get().highlight());
+        f.render_stateful_widget(people, body[0], &mut self.people.state);
+
+        // everything with the selected person in the window, newest first
+        let (title, history) = match self.selected() {
+            Some(person) => (
+                format!("History with {}", person.display_name),
+                payments(&self.stories, self.window.as_ref())
+                    .into_iter()
+                    .rev()
+                    .filter(|(s, _, _)| {
+                        s.counterparty()
+                            .is_some_and(|p| p.username == person.username)
+                    })
+                    .map(|(s, day, cents)| {
+                        ListItem::new(Spans::from(vec![
+                            Span::raw(format!(
+                                "{}  ",
+                                s.local_time()
+                                    .map(|t| t.format(&config::get().date_format).to_string())
+                                    .unwrap_or_else(|| day.to_string())
+                            )),
+                            Span::styled(
+                                format!("{:>10}", format_dollars(cents)),
+                                Style::default().fg(theme::get().amount(cents)),
+                            ),
+                            Span::raw(format!(
+                                "  {}",
+                                s.note.content.as_deref().unwrap_or_default()
+                            )),
+                        ]))
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            None => ("History".to_string(), vec![]),
+        };
+        f.render_widget(
+            List::new(history).block(Block::default().borders(Borders::ALL).title(title)),
+            body[1],
+        );
+    }
+}
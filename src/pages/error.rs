@@ -1,58 +1,101 @@
-use std::io::StdoutLock;
+use std::io;
 
-use async_trait::async_trait;
+use crossterm::event::Event;
 use tui::{
-    backend::CrosstermBackend,
+    backend::Backend,
     layout::Rect,
     style::Style,
     text::{Span, Spans},
-    widgets::{Block, Borders, Clear, Paragraph},
-    Frame,
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame, Terminal,
 };
 use tui_textarea::Input;
 
-use crate::api::Api;
+use crate::{
+    keymap::{self, Action},
+    theme,
+};
 
-use super::{centered_rect, Page};
+use super::centered_rect;
 
-pub struct ErrorPage<'a, 'b> {
-    api: &'a mut Api,
-    msg: &'b str,
+pub enum Response {
+    Retry,
+    Dismiss,
 }
 
-impl<'a, 'b> ErrorPage<'a, 'b> {
-    pub fn new(api: &'a mut Api, msg: &'b str) -> Self {
-        Self { api, msg }
-    }
+// a modal for a failure, shown over whatever was on screen
+pub struct ErrorPage {
+    msg: String,
+    // whether the failed work is still pending and can be tried again
+    retry: bool,
 }
 
-#[async_trait]
-impl<'a, 'b> Page for ErrorPage<'a, 'b> {
-    async fn on_input_event(&mut self, _event: Input) -> bool {
-        // on any keystroke, exit
-        true
+impl ErrorPage {
+    pub fn new(msg: impl Into<String>, retry: bool) -> Self {
+        Self {
+            msg: msg.into(),
+            retry,
+        }
     }
 
-    async fn make_progress(&mut self) -> bool {
-        false
+    pub fn input(&self, input: &Input) -> Option<Response> {
+        match keymap::get().action(input, false) {
+            Some(Action::Submit) if self.retry => Some(Response::Retry),
+            Some(Action::Back) => Some(Response::Dismiss),
+            _ if self.retry => None,
+            // nothing to retry, so any key will do
+            _ => Some(Response::Dismiss),
+        }
     }
 
-    fn render(&mut self, f: &mut Frame<CrosstermBackend<StdoutLock>>, area: Rect) {
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
         let popup = centered_rect(50, 30, area);
         f.render_widget(Clear, popup);
 
-        let text = Paragraph::new(vec![Spans::from(Span::styled(
-            self.msg,
-            Style::default().fg(tui::style::Color::Red),
-        ))])
+        let hint = if self.retry {
+            format!(
+                "{}: retry   {}: dismiss",
+                keymap::get().describe(Action::Submit),
+                keymap::get().describe(Action::Back)
+            )
+        } else {
+            "press any key".to_string()
+        };
+
+        let text = Paragraph::new(vec![
+            Spans::from(Span::styled(
+                self.msg.as_str(),
+                Style::default().fg(theme::get().error),
+            )),
+            Spans::from(""),
+            Spans::from(Span::styled(hint, Style::default().fg(theme::get().muted))),
+        ])
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .title("ERROR")
-                .style(Style::default().bg(tui::style::Color::White)),
+                .style(Style::default().bg(theme::get().surface)),
         )
-        .alignment(tui::layout::Alignment::Center);
+        .alignment(tui::layout::Alignment::Center)
+        .wrap(Wrap { trim: true });
 
         f.render_widget(text, popup);
     }
 }
+
+// for failures outside of any page, e.g. while starting up: the modal on its own until it's answered
+pub async fn show_error<T: Backend>(
+    term: &mut Terminal<T>,
+    msg: impl Into<String>,
+    retry: bool,
+) -> io::Result<Response> {
+    let page = ErrorPage::new(msg, retry);
+    loop {
+        term.draw(|f| page.render(f, f.size()))?;
+        if let Event::Key(key) = crossterm::event::read()? {
+            if let Some(response) = page.input(&Event::Key(key).into()) {
+                return Ok(response);
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::io::Stdout;
+
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Rect},
+    style::Style,
+    widgets::{Block, Borders, Cell, Clear, Row, Table},
+    Frame,
+};
+
+use crate::theme;
+
+use super::centered_rect;
+
+// the `?` overlay: what the keys do for whatever has focus
+pub struct Help {
+    lines: Vec<(String, &'static str)>,
+}
+
+impl Help {
+    pub fn new(lines: Vec<(String, &'static str)>) -> Self {
+        // unbound actions have nothing to show
+        let lines = lines.into_iter().filter(|(k, _)| !k.is_empty()).collect();
+        Self { lines }
+    }
+
+    pub fn render(&self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
+        let area = centered_rect(60, 70, area);
+        f.render_widget(Clear, area);
+
+        let rows = self.lines.iter().map(|(keys, what)| {
+            Row::new(vec![
+                Cell::from(keys.as_str()).style(Style::default().fg(theme::get().accent)),
+                Cell::from(*what).style(Style::default().fg(theme::get().text)),
+            ])
+        });
+        let table = Table::new(rows)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Keys (any key to close)")
+                    .border_style(Style::default().fg(theme::get().accent))
+                    .style(Style::default().bg(theme::get().surface)),
+            )
+            .column_spacing(2)
+            .widths(&[Constraint::Percentage(35), Constraint::Percentage(65)]);
+        f.render_widget(table, area);
+    }
+}
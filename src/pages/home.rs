@@ -1,22 +1,49 @@
-use std::io::{self, StdoutLock};
+use std::{
+    fmt,
+    io::{self, Stdout},
+};
 
+use crossterm::event::{Event, MouseButton, MouseEvent, MouseEventKind};
 use tui::{
     backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::Style,
     text::{Span, Spans},
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
     Terminal,
 };
-use tui_textarea::{Input, Key};
+use tui_textarea::Input;
 
-use crate::{api::Api, types::LoginResponse};
+use crate::{
+    api::Api,
+    config, crash,
+    keymap::{self, Action},
+    theme,
+};
 
-use super::{me::MePage, stories::StoriesPage, Page, ASCII_TITLE};
+use super::{
+    analytics::AnalyticsPage,
+    balances::BalancesPage,
+    bound,
+    error::{ErrorPage, Response},
+    first_visible,
+    help::Help,
+    hit,
+    me::MePage,
+    palette::{Outcome, Palette},
+    row_at,
+    stories::StoriesPage,
+    toast::Toasts,
+    wallet::WalletPage,
+    Navigate, Page, ASCII_TITLE,
+};
 
 pub struct StatefulList<T> {
     pub state: ListState,
     pub items: Vec<T>,
+    // where the list was last drawn, for clicks
+    area: Rect,
+    offset: usize,
 }
 
 impl<T> StatefulList<T> {
@@ -28,10 +55,33 @@ impl<T> StatefulList<T> {
                 s
             },
             items,
+            area: Rect::default(),
+            offset: 0,
         }
     }
 
+    // call when drawing the list into `area`, borders included, so clicks can find their item
+    pub fn track(&mut self, area: Rect) {
+        let heights = vec![1; self.items.len()];
+        let inner = area.height.saturating_sub(2);
+        self.offset = first_visible(&heights, 0, self.state.selected(), self.offset, inner);
+        self.area = area;
+    }
+
+    // the item under a click, if any
+    pub fn item_at(&self, event: &MouseEvent) -> Option<usize> {
+        if !hit(self.area, event) {
+            return None;
+        }
+        let y = event.row.checked_sub(self.area.y + 1)?;
+        let heights = vec![1; self.items.len()];
+        row_at(&heights, 0, self.offset, y)
+    }
+
     pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.items.len() - 1 {
@@ -47,6 +97,9 @@ impl<T> StatefulList<T> {
     }
 
     pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -59,29 +112,49 @@ impl<T> StatefulList<T> {
         };
         self.state.select(Some(i));
     }
+
+    pub fn first(&mut self) {
+        self.state.select(Some(0));
+    }
+
+    pub fn last(&mut self) {
+        self.state.select(Some(self.items.len().saturating_sub(1)));
+    }
+
+    // move n items down without wrapping
+    pub fn forward(&mut self, n: usize) {
+        let i = self.state.selected().map_or(0, |i| i + n);
+        self.state
+            .select(Some(i.min(self.items.len().saturating_sub(1))));
+    }
+
+    pub fn back(&mut self, n: usize) {
+        let i = self.state.selected().map_or(0, |i| i.saturating_sub(n));
+        self.state.select(Some(i));
+    }
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Default)]
 enum CurrentPage {
+    #[default]
     Home,
     Transactions,
+    Analytics,
+    Balances,
+    Wallet,
     Logout,
 }
 
-impl Default for CurrentPage {
-    fn default() -> Self {
-        CurrentPage::Home
-    }
-}
-
-impl ToString for CurrentPage {
-    fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for CurrentPage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
             CurrentPage::Home => "Home",
             CurrentPage::Transactions => "Transactions",
+            CurrentPage::Analytics => "Analytics",
+            CurrentPage::Balances => "Balances",
+            CurrentPage::Wallet => "Wallet",
             CurrentPage::Logout => "Logout",
-        }
-        .to_string()
+        })
     }
 }
 
@@ -95,26 +168,55 @@ impl<'a> SideBar<'a> {
             items: StatefulList::with_items(vec![
                 ("Home", CurrentPage::Home),
                 ("Transactions", CurrentPage::Transactions),
+                ("Analytics", CurrentPage::Analytics),
+                ("Balances", CurrentPage::Balances),
+                ("Wallet", CurrentPage::Wallet),
                 ("Logout", CurrentPage::Logout),
             ]),
         }
     }
 }
 
+// why the home page closed
+pub enum Exit {
+    Quit,
+    // logged out, back to the login page
+    SwitchAccount,
+}
+
 enum FocusedArea {
     SideBar,
     MainWindow,
 }
 
 pub async fn draw_home_page(
-    term: &mut Terminal<CrosstermBackend<StdoutLock<'_>>>,
-    api: &'static mut Api,
-) -> io::Result<Option<LoginResponse>> {
+    term: &mut Terminal<CrosstermBackend<Stdout>>,
+    api: &mut Api,
+) -> io::Result<Exit> {
     let mut focused_area = FocusedArea::SideBar;
 
     let mut side_bar = SideBar::new();
-    let (mut assoc_index, mut current_page): (usize, Option<Box<dyn Page>>) =
+
+    // offline, nothing can be changed, so say how stale the cached data is
+    let offline = api.offline;
+    let sidebar_width = config::get().layout.sidebar_width;
+    let offline_banner = Paragraph::new(format!(
+        "OFFLINE: read-only, showing data last synced {}",
+        api.cache
+            .synced_at()
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "never".to_string())
+    ))
+    .style(
+        Style::default()
+            .fg(theme::get().warning_fg)
+            .bg(theme::get().warning_bg),
+    )
+    .alignment(Alignment::Center);
+
+    let (mut assoc_index, mut current_page): (usize, Option<Box<dyn Page + '_>>) =
         (0, Some(Box::new(MePage::new(api))));
+    crash::set_page("Home");
 
     let venmo_text_big = Paragraph::new(
         ASCII_TITLE
@@ -123,25 +225,46 @@ pub async fn draw_home_page(
             .map(|l| Spans::from(Span::styled(l, Style::default())))
             .collect::<Vec<_>>(),
     )
-    .style(Style::default().fg(Color::Blue))
+    .style(Style::default().fg(theme::get().accent))
     .block(Block::default())
     .alignment(Alignment::Left);
 
     let outer_layout = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(8), Constraint::Min(8)].as_ref());
+        .constraints(
+            [
+                Constraint::Length(8),
+                Constraint::Length(if offline { 1 } else { 0 }),
+                Constraint::Min(8),
+            ]
+            .as_ref(),
+        );
 
     let layout = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(10), Constraint::Percentage(90)].as_ref());
+        .constraints(
+            [
+                Constraint::Percentage(sidebar_width),
+                Constraint::Percentage(100 - sidebar_width),
+            ]
+            .as_ref(),
+        );
 
     let items: Vec<ListItem> = side_bar
         .items
         .items
         .iter()
-        .map(|i| ListItem::new(Spans::from(i.0)).style(Style::default().fg(Color::Black)))
+        .map(|i| ListItem::new(Spans::from(i.0)).style(Style::default().fg(theme::get().text)))
         .collect();
 
+    let mut page_area = Rect::default();
+    let mut help: Option<Help> = None;
+    let mut palette: Option<Palette> = None;
+    let mut error: Option<ErrorPage> = None;
+    let mut toasts = Toasts::default();
+    // a command for a page that's opened at the top of the next loop
+    let mut pending = None;
+
     'outer: loop {
         if let Some(selected) = side_bar.items.state.selected() {
             if selected != assoc_index {
@@ -149,97 +272,327 @@ pub async fn draw_home_page(
                 current_page = match side_bar.items.items[selected].1 {
                     CurrentPage::Home => Some(Box::new(MePage::new(api))),
                     CurrentPage::Transactions => Some(Box::new(StoriesPage::new(api))),
+                    CurrentPage::Analytics => Some(Box::new(AnalyticsPage::new(api))),
+                    CurrentPage::Balances => Some(Box::new(BalancesPage::new(api))),
+                    CurrentPage::Wallet => Some(Box::new(WalletPage::new(api))),
                     CurrentPage::Logout => None,
                 };
+                crash::set_page(side_bar.items.items[selected].0);
                 assoc_index = selected;
+                if let (Some(action), Some(p)) = (pending.take(), &mut current_page) {
+                    p.on_action(action);
+                }
             }
         }
 
+        if let Some(notice) = current_page.as_mut().and_then(|p| p.notice()) {
+            toasts.push(notice);
+        }
+        if let Some(failure) = current_page.as_mut().and_then(|p| p.failure()) {
+            toasts.push_error(failure);
+        }
+
         term.draw(|f| {
             let outer_chunks = outer_layout.split(f.size());
 
             f.render_widget(venmo_text_big.clone(), outer_chunks[0]);
 
-            let chunks = layout.split(outer_chunks[1]);
+            if offline {
+                f.render_widget(offline_banner.clone(), outer_chunks[1]);
+            }
+
+            let chunks = layout.split(outer_chunks[2]);
 
             {
                 // Create a List from all list items and highlight the currently selected one
                 let items = List::new(items.clone())
                     .block(Block::default().borders(Borders::ALL))
-                    .highlight_style(
-                        Style::default()
-                            .fg(Color::Blue)
-                            .add_modifier(Modifier::BOLD),
-                    );
+                    .highlight_style(theme::get().highlight());
+                side_bar.items.track(chunks[0]);
                 f.render_stateful_widget(items, chunks[0], &mut side_bar.items.state);
             }
 
-            if let Some(_) = side_bar.items.state.selected() {
+            page_area = chunks[1];
+            if side_bar.items.state.selected().is_some() {
                 if let Some(ref mut p) = &mut current_page {
                     p.render(f, chunks[1]);
                 }
             }
+
+            if let Some(help) = &help {
+                help.render(f, f.size());
+            }
+            if let Some(palette) = &mut palette {
+                palette.render(f, f.size());
+            }
+            if let Some(error) = &error {
+                error.render(f, f.size());
+            }
+            toasts.render(f, f.size());
         })?;
 
-        if let Some(ref mut p) = &mut current_page {
-            if p.make_progress().await {
+        // nothing moves while an error is up
+        if error.is_none() {
+            if let Some(ref mut p) = &mut current_page {
+                match p.make_progress().await {
+                    Ok(true) => continue,
+                    Ok(false) => {}
+                    Err(e) => {
+                        error = Some(ErrorPage::new(e.to_string(), true));
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // wake up to take down an expired toast, or when the page has work waiting on a timer
+        let wake = current_page
+            .as_ref()
+            .filter(|_| error.is_none())
+            .and_then(|p| p.wake_in());
+        if let Some(timeout) = [toasts.remaining(), wake].into_iter().flatten().min() {
+            if !crossterm::event::poll(timeout)? {
+                if toasts.remaining().is_some_and(|r| r.is_zero()) {
+                    toasts.expire();
+                }
                 continue;
             }
         }
 
-        match focused_area {
-            FocusedArea::SideBar => match crossterm::event::read()?.into() {
-                Input { key: Key::Esc, .. } => break 'outer,
-                Input { key: Key::Down, .. } => side_bar.items.next(),
-                Input { key: Key::Up, .. } => side_bar.items.previous(),
-                Input {
-                    key: Key::Right, ..
-                } => {
-                    if let Some(selected) = side_bar.items.state.selected() {
-                        if side_bar.items.items[selected].1 != CurrentPage::Logout {
-                            focused_area = FocusedArea::MainWindow;
+        let on_logout = side_bar
+            .items
+            .state
+            .selected()
+            .is_some_and(|i| side_bar.items.items[i].1 == CurrentPage::Logout);
+
+        let mut exit = false;
+        let mut navigation = None;
+        let mut command = None;
+        let event = crossterm::event::read()?;
+
+        if let Some(e) = &error {
+            if let Event::Key(_) = event {
+                match e.input(&event.into()) {
+                    // the page's work is still pending, so it runs again
+                    Some(Response::Retry) => error = None,
+                    Some(Response::Dismiss) => {
+                        error = None;
+                        if let Some(ref mut p) = &mut current_page {
+                            p.cancel();
                         }
                     }
+                    None => {}
                 }
-                Input {
-                    key: Key::Enter, ..
-                } => {
-                    if let Some(selected) = side_bar.items.state.selected() {
-                        match side_bar.items.items[selected].1 {
-                            CurrentPage::Logout => {
-                                drop(current_page);
-                                api.logout().await.expect("failed to logout ugh :(");
-                                break 'outer;
-                            }
-                            _ => {}
-                        }
+            }
+            continue;
+        }
+
+        if let Some(p) = &mut palette {
+            let outcome = match event {
+                Event::Mouse(mouse) => p.on_mouse_event(mouse),
+                Event::Key(_) => p.input(event.clone().into()),
+                _ => Outcome::Open,
+            };
+            match outcome {
+                Outcome::Open => continue,
+                Outcome::Close => {
+                    palette = None;
+                    continue;
+                }
+                Outcome::Run(action) => {
+                    palette = None;
+                    command = Some(action);
+                }
+            }
+        }
+
+        // any key or click closes the help
+        if help.is_some() {
+            if let Event::Key(_)
+            | Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(_),
+                ..
+            }) = event
+            {
+                help = None;
+            }
+            continue;
+        }
+
+        match (event, &focused_area) {
+            _ if command.is_some() => {}
+            (Event::Mouse(mouse), _) if hit(side_bar.items.area, &mouse) => match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left) => {
+                    if let Some(i) = side_bar.items.item_at(&mouse) {
+                        side_bar.items.state.select(Some(i));
                     }
+                    focused_area = FocusedArea::SideBar;
                 }
+                MouseEventKind::ScrollDown => side_bar.items.next(),
+                MouseEventKind::ScrollUp => side_bar.items.previous(),
                 _ => {}
             },
-            FocusedArea::MainWindow => {
-                let event: Input = crossterm::event::read()?.into();
-
-                if let Some(_selected) = side_bar.items.state.selected() {
-                    if let Some(ref mut p) = &mut current_page {
-                        if !p.on_input_event(event.clone()).await {
-                            continue;
-                        };
+            (Event::Mouse(mouse), _) if hit(page_area, &mouse) => {
+                if let Some(ref mut p) = &mut current_page {
+                    if let MouseEventKind::Down(_) = mouse.kind {
+                        focused_area = FocusedArea::MainWindow;
                     }
+                    p.on_mouse_event(mouse).await;
+                    navigation = p.navigate();
                 }
-
-                // if escape, exit
-                // if left, go back to side bar
-                match event {
-                    Input { key: Key::Esc, .. } => break 'outer,
-                    Input { key: Key::Left, .. } => {
-                        focused_area = FocusedArea::SideBar;
+            }
+            (Event::Mouse(_), _) => {}
+            (event, FocusedArea::SideBar) => {
+                let event: Input = event.into();
+                match keymap::get().action(&event, false) {
+                    Some(action) if action.is_global() || Action::COMMANDS.contains(&action) => {
+                        command = Some(action)
+                    }
+                    Some(Action::NextItem) => side_bar.items.next(),
+                    Some(Action::PrevItem) => side_bar.items.previous(),
+                    Some(Action::FirstItem) => side_bar.items.first(),
+                    Some(Action::LastItem) => side_bar.items.last(),
+                    Some(Action::Submit) if on_logout && !offline => {
+                        current_page = None;
+                        match api.logout().await {
+                            Ok(_) => break 'outer,
+                            Err(e) => error = Some(ErrorPage::new(e.to_string(), false)),
+                        }
+                    }
+                    Some(Action::Right) | Some(Action::Submit) if !on_logout => {
+                        focused_area = FocusedArea::MainWindow;
                     }
                     _ => {}
-                };
+                }
             }
+            (event, FocusedArea::MainWindow) => {
+                let event: Input = event.into();
+
+                let typing = current_page.as_ref().is_some_and(|p| p.typing());
+                match keymap::get().action(&event, typing) {
+                    Some(action) if action.is_global() => command = Some(action),
+                    _ => {
+                        exit = true;
+                        if let Some(_selected) = side_bar.items.state.selected() {
+                            if let Some(ref mut p) = &mut current_page {
+                                exit = p.on_input_event(event).await;
+                                navigation = p.navigate();
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(command) = command {
+            match command {
+                Action::Quit => break 'outer,
+                Action::Help => {
+                    let mut lines = match focused_area {
+                        FocusedArea::SideBar => vec![
+                            bound(Action::NextItem, "Next page"),
+                            bound(Action::PrevItem, "Previous page"),
+                            bound(Action::Submit, "Open the page"),
+                            bound(Action::Right, "Open the page"),
+                        ],
+                        FocusedArea::MainWindow => {
+                            current_page.as_ref().map(|p| p.help()).unwrap_or_default()
+                        }
+                    };
+                    lines.extend([
+                        bound(Action::Help, "Show key bindings"),
+                        bound(Action::Palette, "Command palette"),
+                        bound(Action::Refresh, "Refresh"),
+                        bound(Action::Quit, "Quit"),
+                    ]);
+                    help = Some(Help::new(lines));
+                }
+                Action::Palette => palette = Some(Palette::new(offline)),
+                Action::Refresh => {
+                    current_page = None;
+                    if !offline {
+                        if let Err(e) = api.get_profile().await {
+                            error = Some(ErrorPage::new(e.to_string(), false));
+                        }
+                    }
+                    // rebuilt at the top of the loop, and told to refetch what it keeps cached
+                    assoc_index = usize::MAX;
+                    pending = Some(Action::Refresh);
+                }
+                Action::SwitchAccount if !offline => {
+                    current_page = None;
+                    match api.logout().await {
+                        Ok(_) => return Ok(Exit::SwitchAccount),
+                        Err(e) => {
+                            error = Some(ErrorPage::new(e.to_string(), false));
+                            // rebuilt at the top of the loop
+                            assoc_index = usize::MAX;
+                        }
+                    }
+                }
+                action => {
+                    // the rest are for a page, so open it first if need be
+                    let target = match action {
+                        Action::GoHome | Action::Pay => CurrentPage::Home,
+                        Action::GoTransactions | Action::Search | Action::Export => {
+                            CurrentPage::Transactions
+                        }
+                        Action::GoAnalytics => CurrentPage::Analytics,
+                        Action::GoBalances => CurrentPage::Balances,
+                        Action::GoWallet => CurrentPage::Wallet,
+                        _ => continue,
+                    };
+                    if let Some(index) = side_bar.items.items.iter().position(|i| i.1 == target) {
+                        focused_area = FocusedArea::MainWindow;
+                        if index == assoc_index {
+                            if let Some(ref mut p) = &mut current_page {
+                                p.on_action(action);
+                            }
+                        } else {
+                            side_bar.items.state.select(Some(index));
+                            pending = Some(action);
+                        }
+                    }
+                }
+            }
+            continue;
+        }
+
+        if let Some(navigation) = navigation {
+            let target = match navigation {
+                Navigate::Transactions(_) => CurrentPage::Transactions,
+                Navigate::Pay { .. } => CurrentPage::Home,
+            };
+
+            if let Some(index) = side_bar.items.items.iter().position(|i| i.1 == target) {
+                drop(current_page);
+                current_page = Some(match navigation {
+                    Navigate::Transactions(query) => Box::new(StoriesPage::with_query(api, &query)),
+                    Navigate::Pay {
+                        handle,
+                        amount_cents,
+                        note,
+                        payment_type,
+                    } => Box::new(MePage::with_payment(
+                        api,
+                        &handle,
+                        amount_cents,
+                        &note,
+                        payment_type,
+                    )),
+                });
+                side_bar.items.state.select(Some(index));
+                crash::set_page(side_bar.items.items[index].0);
+                assoc_index = index;
+            }
+            continue;
+        }
+
+        // the page is done with the keyboard, go back to the side bar
+        if exit {
+            focused_area = FocusedArea::SideBar;
         }
     }
 
-    Ok(None)
+    Ok(Exit::Quit)
 }
@@ -1,18 +1,25 @@
 use std::io;
 
+use crossterm::event::Event;
 use tui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
+    style::Style,
     text::{Span, Spans, Text},
     widgets::{Block, Borders, Paragraph, Wrap},
     Terminal,
 };
-use tui_textarea::{Input, Key, TextArea};
+use tui_textarea::{Input, TextArea};
 
-use crate::{api::Api, types::LoginResponse};
+use crate::{
+    api::Api,
+    crash,
+    keymap::{self, Action},
+    theme,
+    types::LoginResponse,
+};
 
-use super::{activate, inactivate, ASCII_TITLE};
+use super::{activate, error::ErrorPage, inactivate, ASCII_TITLE};
 
 enum LoginField {
     Username,
@@ -27,6 +34,7 @@ pub async fn draw_login_page<T>(
 where
     T: Backend,
 {
+    crash::set_page("Login");
     let [mut username, mut password] = [TextArea::default(), TextArea::default()];
 
     // set titles
@@ -58,12 +66,13 @@ where
         .collect::<Vec<_>>();
 
     let mut selected: LoginField = LoginField::Username;
+    let mut error: Option<ErrorPage> = None;
 
     activate(&mut username);
     inactivate(&mut password);
 
     let title = Paragraph::new(text.clone())
-        .style(Style::default().fg(Color::Blue))
+        .style(Style::default().fg(theme::get().accent))
         .block(Block::default())
         .alignment(Alignment::Center);
 
@@ -83,10 +92,27 @@ where
             f.render_widget(p_widget, chunks[2]);
 
             f.render_widget(login_btn_text.clone(), chunks[3]);
+
+            if let Some(error) = &error {
+                error.render(f, f.size());
+            }
         })?;
-        match crossterm::event::read()?.into() {
-            Input { key: Key::Esc, .. } => break,
-            Input { key: Key::Down, .. } => match selected {
+
+        let event = crossterm::event::read()?;
+        if let Some(e) = &error {
+            if let Event::Key(_) = event {
+                if e.input(&event.into()).is_some() {
+                    error = None;
+                }
+            }
+            continue;
+        }
+
+        let input: Input = event.into();
+        let typing = !matches!(selected, LoginField::Login);
+        match keymap::get().action(&input, typing) {
+            Some(Action::Back) | Some(Action::Quit) => break,
+            Some(Action::NextItem) => match selected {
                 LoginField::Username => {
                     inactivate(&mut username);
                     activate(&mut password);
@@ -94,16 +120,12 @@ where
                 }
                 LoginField::Password => {
                     inactivate(&mut password);
-                    login_btn_text = login_btn_text.style(
-                        Style::default()
-                            .fg(Color::Blue)
-                            .add_modifier(Modifier::BOLD),
-                    );
+                    login_btn_text = login_btn_text.style(theme::get().highlight());
                     selected = LoginField::Login;
                 }
                 _ => {}
             },
-            Input { key: Key::Up, .. } => match selected {
+            Some(Action::PrevItem) => match selected {
                 LoginField::Password => {
                     inactivate(&mut password);
                     activate(&mut username);
@@ -116,9 +138,7 @@ where
                 }
                 _ => {}
             },
-            Input {
-                key: Key::Enter, ..
-            } => match selected {
+            Some(Action::Submit) => match selected {
                 LoginField::Username => {
                     inactivate(&mut username);
                     activate(&mut password);
@@ -126,24 +146,17 @@ where
                 }
                 LoginField::Password => {
                     inactivate(&mut password);
-                    login_btn_text = login_btn_text.style(
-                        Style::default()
-                            .fg(Color::Blue)
-                            .add_modifier(Modifier::BOLD),
-                    );
+                    login_btn_text = login_btn_text.style(theme::get().highlight());
                     selected = LoginField::Login;
                 }
                 LoginField::Login => {
                     match api.login(&username.lines()[0], &password.lines()[0]).await {
-                        Err(e) => {
-                            // draw error
-                            panic!("{}", e);
-                        }
+                        Err(e) => error = Some(ErrorPage::new(e.to_string(), false)),
                         Ok(v) => return Ok(Some(v)),
                     }
                 }
             },
-            input => {
+            _ => {
                 match selected {
                     LoginField::Username => username.input(input),
                     LoginField::Password => password.input(input),
@@ -1,6 +1,7 @@
-use std::io::StdoutLock;
+use std::{io::Stdout, time::Duration};
 
 use async_trait::async_trait;
+use crossterm::event::MouseEvent;
 use tui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
@@ -11,7 +12,11 @@ use tui::{
 };
 use tui_textarea::Input;
 
-use crate::api::Api;
+use crate::{
+    api::{Api, ApiError, PaymentType},
+    keymap::Action,
+    types::format_dollars,
+};
 
 use super::{pay::PayPage, qr, Page};
 
@@ -24,13 +29,35 @@ pub struct MePage<'a> {
 
 impl<'a> MePage<'a> {
     pub fn new(api: &'a mut Api) -> Self {
+        let (handle, display_name, balance) = match &api.identity {
+            Some(me) => (
+                me.handle.clone(),
+                me.display_name.clone(),
+                me.balance.user_balance.value,
+            ),
+            // not loaded yet, so there's nothing to show but the pay form
+            None => Default::default(),
+        };
         Self {
-            handle: api.identity.as_ref().unwrap().handle.clone(),
-            display_name: api.identity.as_ref().unwrap().display_name.clone(),
-            balance: api.identity.as_ref().unwrap().balance.user_balance.value,
+            handle,
+            display_name,
+            balance,
             pay_page: PayPage::new(api),
         }
     }
+
+    pub fn with_payment(
+        api: &'a mut Api,
+        handle: &str,
+        amount_cents: i64,
+        note: &str,
+        payment_type: PaymentType,
+    ) -> Self {
+        let mut page = Self::new(api);
+        page.pay_page
+            .prefill(handle, amount_cents, note, payment_type);
+        page
+    }
 }
 
 #[async_trait]
@@ -39,11 +66,43 @@ impl<'a> Page for MePage<'a> {
         self.pay_page.on_input_event(event).await
     }
 
-    async fn make_progress(&mut self) -> bool {
+    async fn on_mouse_event(&mut self, event: MouseEvent) {
+        self.pay_page.on_mouse_event(event).await
+    }
+
+    fn typing(&self) -> bool {
+        self.pay_page.typing()
+    }
+
+    fn help(&self) -> Vec<(String, &'static str)> {
+        self.pay_page.help()
+    }
+
+    fn on_action(&mut self, action: Action) -> bool {
+        self.pay_page.on_action(action)
+    }
+
+    async fn make_progress(&mut self) -> Result<bool, ApiError> {
         self.pay_page.make_progress().await
     }
 
-    fn render(&mut self, f: &mut Frame<CrosstermBackend<StdoutLock>>, area: Rect) {
+    fn cancel(&mut self) {
+        self.pay_page.cancel()
+    }
+
+    fn wake_in(&self) -> Option<Duration> {
+        self.pay_page.wake_in()
+    }
+
+    fn notice(&mut self) -> Option<String> {
+        self.pay_page.notice()
+    }
+
+    fn failure(&mut self) -> Option<String> {
+        self.pay_page.failure()
+    }
+
+    fn render(&mut self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
         let inner_layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(60), Constraint::Length(30)].as_ref())
@@ -57,13 +116,16 @@ impl<'a> Page for MePage<'a> {
 
             let text = Paragraph::new(vec![
                 Spans::from(Span::styled(
-                    format!("{}", self.display_name),
+                    self.display_name.to_string(),
                     Style::default().add_modifier(Modifier::BOLD),
                 )),
                 Spans::from(Span::styled(format!("@{}", self.handle), Style::default())),
                 Spans::from(Span::styled("", Style::default())),
                 Spans::from(Span::styled(
-                    format!("Balance: ${}", self.balance),
+                    format!(
+                        "Balance: {}",
+                        format_dollars((self.balance as f64 * 100.0).round() as i64)
+                    ),
                     Style::default().add_modifier(Modifier::BOLD),
                 )),
             ])
@@ -1,24 +1,40 @@
-use std::io::StdoutLock;
+use std::{
+    io::{self, Stdout, Write},
+    time::Duration,
+};
 
 use async_trait::async_trait;
+use crossterm::event::MouseEvent;
 use tui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     widgets::{Block, Borders},
     Frame,
 };
 use tui_textarea::{Input, TextArea};
 
+use crate::{
+    api::{ApiError, PaymentType},
+    keymap::{self, Action},
+    theme,
+};
+
+pub mod analytics;
+pub mod balances;
+pub mod error;
+pub mod help;
 pub mod home;
 pub mod login;
 pub mod me;
+pub mod palette;
 pub mod pay;
 pub mod qr;
 pub mod stories;
-pub mod error;
+pub mod toast;
+pub mod wallet;
 
-const ASCII_TITLE: &'static str = r#"
+const ASCII_TITLE: &str = r#"
  __      __                        
  \ \    / /                        
   \ \  / /__ _ __  _ __ ___   ___  
@@ -27,13 +43,64 @@ const ASCII_TITLE: &'static str = r#"
      \/ \___|_| |_|_| |_| |_|\___/ 
 "#;
 
+// a request from a page to show another one
+pub enum Navigate {
+    // the story list, filtered by a search query
+    Transactions(String),
+    // the pay form, filled in for review
+    Pay {
+        handle: String,
+        amount_cents: i64,
+        note: String,
+        payment_type: PaymentType,
+    },
+}
+
 #[async_trait]
-trait Page {
+trait Page: Send {
     // return true if exit
     async fn on_input_event(&mut self, event: Input) -> bool;
-    // return true if progress made (skip block for input)
-    async fn make_progress(&mut self) -> bool;
-    fn render(&mut self, f: &mut Frame<CrosstermBackend<StdoutLock>>, area: Rect);
+    // return true if progress made (skip block for input). on error the work is left pending, so
+    // it's tried again unless the user dismisses it
+    async fn make_progress(&mut self) -> Result<bool, ApiError>;
+    // drop whatever work failed in make_progress
+    fn cancel(&mut self) {}
+    // how long until make_progress has work to do with no input, e.g. a debounced search
+    fn wake_in(&self) -> Option<Duration> {
+        None
+    }
+    // a success worth telling the user about, e.g. a sent payment
+    fn notice(&mut self) -> Option<String> {
+        None
+    }
+    // something that went wrong outside make_progress, e.g. on a key press, with nothing to retry
+    fn failure(&mut self) -> Option<String> {
+        None
+    }
+    fn render(&mut self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect);
+    // clicks and scrolls inside the page's area
+    async fn on_mouse_event(&mut self, _event: MouseEvent) {}
+    // true while an input has focus, so plain character keys are typed rather than bound
+    fn typing(&self) -> bool {
+        false
+    }
+    // checked after each input event
+    fn navigate(&mut self) -> Option<Navigate> {
+        None
+    }
+    // the page's lines in the help overlay
+    fn help(&self) -> Vec<(String, &'static str)> {
+        vec![]
+    }
+    // a command from the palette meant for the page, false if it isn't one
+    fn on_action(&mut self, _action: Action) -> bool {
+        false
+    }
+}
+
+// a help overlay line for whatever keys are bound to `action`
+fn bound(action: Action, what: &'static str) -> (String, &'static str) {
+    (keymap::get().describe(action), what)
 }
 
 fn inactivate(textarea: &mut TextArea<'_>) {
@@ -43,7 +110,7 @@ fn inactivate(textarea: &mut TextArea<'_>) {
         .block()
         .cloned()
         .unwrap_or_else(|| Block::default().borders(Borders::ALL));
-    textarea.set_block(b.style(Style::default().fg(Color::DarkGray)));
+    textarea.set_block(b.style(Style::default().fg(theme::get().muted)));
 }
 
 fn activate(textarea: &mut TextArea<'_>) {
@@ -53,7 +120,7 @@ fn activate(textarea: &mut TextArea<'_>) {
         .block()
         .cloned()
         .unwrap_or_else(|| Block::default().borders(Borders::ALL));
-    textarea.set_block(b.style(Style::default().fg(Color::Blue)));
+    textarea.set_block(b.style(Style::default().fg(theme::get().accent)));
 }
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
@@ -83,3 +150,178 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .margin(1)
         .split(popup_layout[1])[1]
 }
+
+// whether a click or scroll landed inside a rendered area
+fn hit(area: Rect, event: &MouseEvent) -> bool {
+    (area.left()..area.right()).contains(&event.column)
+        && (area.top()..area.bottom()).contains(&event.row)
+}
+
+// tui keeps the scroll offset of lists and tables private, so this repeats its math to find the
+// first row it will draw. `margin` is the gap under each row
+fn first_visible(
+    heights: &[u16],
+    margin: u16,
+    selected: Option<usize>,
+    offset: usize,
+    max_height: u16,
+) -> usize {
+    if heights.is_empty() {
+        return 0;
+    }
+    let total = |i: usize| heights[i] + margin;
+
+    let mut start = offset.min(heights.len() - 1);
+    let mut end = start;
+    let mut height = 0;
+    for &h in heights.iter().skip(start) {
+        if height + h > max_height {
+            break;
+        }
+        height += h + margin;
+        end += 1;
+    }
+
+    let selected = selected.unwrap_or(0).min(heights.len() - 1);
+    while selected >= end {
+        height = height.saturating_add(total(end));
+        end += 1;
+        while height > max_height {
+            height = height.saturating_sub(total(start));
+            start += 1;
+        }
+    }
+    while selected < start {
+        start -= 1;
+        height = height.saturating_add(total(start));
+        while height > max_height {
+            end -= 1;
+            height = height.saturating_sub(total(end));
+        }
+    }
+    start
+}
+
+// the row drawn `y` lines below the first visible one
+fn row_at(heights: &[u16], margin: u16, offset: usize, y: u16) -> Option<usize> {
+    let mut top = 0;
+    for (i, &h) in heights.iter().enumerate().skip(offset) {
+        if y < top + h {
+            return Some(i);
+        }
+        top += h + margin;
+        if y < top {
+            return None;
+        }
+    }
+    None
+}
+
+// put `text` on the clipboard through the terminal (OSC 52), which also works over ssh
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in text.as_bytes().chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{encoded}\x07")?;
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tui::{
+        backend::TestBackend,
+        buffer::Buffer,
+        widgets::{Cell, List, ListItem, ListState, Row, Table, TableState},
+        Terminal,
+    };
+
+    // down to the bottom, back up, then some jumps
+    fn walk(len: usize) -> Vec<usize> {
+        let mut steps: Vec<usize> = (0..len).chain((0..len).rev()).collect();
+        steps.extend([len - 1, 0, len / 2, len - 2, 1, len / 3]);
+        steps
+    }
+
+    fn line(buffer: &Buffer, y: u16) -> String {
+        (0..buffer.area.width)
+            .map(|x| buffer.get(x, y).symbol.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn first_visible_matches_a_rendered_list() {
+        let len = 25;
+        let height = 8;
+        let mut terminal = Terminal::new(TestBackend::new(12, height + 2)).unwrap();
+        let mut state = ListState::default();
+        let mut offset = 0;
+        for selected in walk(len) {
+            state.select(Some(selected));
+            offset = first_visible(&vec![1; len], 0, Some(selected), offset, height);
+            let items: Vec<ListItem> = (0..len)
+                .map(|i| ListItem::new(format!("i{i:02}")))
+                .collect();
+            let list = List::new(items).block(Block::default().borders(Borders::ALL));
+            terminal
+                .draw(|f| f.render_stateful_widget(list, f.size(), &mut state))
+                .unwrap();
+            let top = line(terminal.backend().buffer(), 1);
+            assert!(
+                top.starts_with(&format!("│i{offset:02}")),
+                "selected {selected}: expected row {offset} on top, got {top:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn first_visible_matches_a_rendered_table_with_tall_rows() {
+        let heights: Vec<u16> = (0..20).map(|i| [1, 2, 1, 3][i % 4]).collect();
+        let len = heights.len();
+        let height = 14;
+        let mut terminal = Terminal::new(TestBackend::new(12, height + 4)).unwrap();
+        let mut state = TableState::default();
+        let mut offset = 0;
+        for selected in walk(len) {
+            state.select(Some(selected));
+            offset = first_visible(&heights, 1, Some(selected), offset, height);
+            let rows: Vec<Row> = heights
+                .iter()
+                .enumerate()
+                .map(|(i, &h)| {
+                    let text = format!("r{i:02}") + &"\n.".repeat(h as usize - 1);
+                    Row::new([Cell::from(text)]).height(h).bottom_margin(1)
+                })
+                .collect();
+            let table = Table::new(rows)
+                .header(Row::new(["head"]).height(1).bottom_margin(1))
+                .block(Block::default().borders(Borders::ALL))
+                .widths(&[Constraint::Percentage(100)]);
+            terminal
+                .draw(|f| f.render_stateful_widget(table, f.size(), &mut state))
+                .unwrap();
+            // under the border, the header and its margin
+            let top = line(terminal.backend().buffer(), 3);
+            assert!(
+                top.starts_with(&format!("│r{offset:02}")),
+                "selected {selected}: expected row {offset} on top, got {top:?}"
+            );
+        }
+    }
+}
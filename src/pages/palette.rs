@@ -0,0 +1,170 @@
+use std::io::Stdout;
+
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Style,
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, List, ListItem},
+    Frame,
+};
+use tui_textarea::{Input, TextArea};
+
+use crate::{
+    keymap::{self, Action},
+    theme,
+};
+
+use super::{activate, centered_rect, hit, home::StatefulList};
+
+pub enum Outcome {
+    Open,
+    Close,
+    Run(Action),
+}
+
+// the `:` popup: fuzzy search over the commands in the keymap's registry
+pub struct Palette<'a> {
+    query: TextArea<'a>,
+    commands: Vec<Action>,
+    matches: StatefulList<Action>,
+    area: Rect,
+}
+
+// how well `query` matches `text` as a subsequence, favouring runs and word starts. None if it
+// doesn't match at all
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut at = 0;
+    let mut last = None;
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let i = at + text[at..].iter().position(|c| *c == q)?;
+        score += 1;
+        if last.is_some_and(|l| l + 1 == i) {
+            score += 3;
+        }
+        if i == 0 || text[i - 1] == ' ' {
+            score += 2;
+        }
+        last = Some(i);
+        at = i + 1;
+    }
+    Some(score)
+}
+
+impl<'a> Palette<'a> {
+    pub fn new(offline: bool) -> Self {
+        let mut query = TextArea::default();
+        query.set_block(Block::default().borders(Borders::ALL).title("Command"));
+        activate(&mut query);
+
+        // nothing to log in to offline
+        let commands = Action::COMMANDS
+            .into_iter()
+            .filter(|a| !(offline && *a == Action::SwitchAccount))
+            .collect::<Vec<_>>();
+
+        Self {
+            query,
+            matches: StatefulList::with_items(commands.clone()),
+            commands,
+            area: Rect::default(),
+        }
+    }
+
+    fn update_matches(&mut self) {
+        let query = &self.query.lines()[0];
+        let mut scored = self
+            .commands
+            .iter()
+            .filter_map(|a| fuzzy_score(query, a.description()).map(|s| (s, *a)))
+            .collect::<Vec<_>>();
+        // stable, so ties keep the registry's order
+        scored.sort_by_key(|(s, _)| -s);
+        self.matches = StatefulList::with_items(scored.into_iter().map(|(_, a)| a).collect());
+    }
+
+    pub fn input(&mut self, input: Input) -> Outcome {
+        let any = !self.matches.items.is_empty();
+        match keymap::get().action(&input, true) {
+            Some(Action::Back) => return Outcome::Close,
+            Some(Action::Quit) => return Outcome::Run(Action::Quit),
+            Some(Action::NextItem) if any => self.matches.next(),
+            Some(Action::PrevItem) if any => self.matches.previous(),
+            Some(Action::Submit) => {
+                return match self.matches.state.selected() {
+                    Some(i) if any => Outcome::Run(self.matches.items[i]),
+                    _ => Outcome::Open,
+                };
+            }
+            _ => {
+                if self.query.input(input) {
+                    self.update_matches();
+                }
+            }
+        }
+        Outcome::Open
+    }
+
+    // clicking a command runs it, clicking anywhere else closes the palette
+    pub fn on_mouse_event(&mut self, event: MouseEvent) -> Outcome {
+        match event.kind {
+            MouseEventKind::ScrollDown if !self.matches.items.is_empty() => self.matches.next(),
+            MouseEventKind::ScrollUp if !self.matches.items.is_empty() => self.matches.previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                return match self.matches.item_at(&event) {
+                    Some(i) => Outcome::Run(self.matches.items[i]),
+                    None if hit(self.area, &event) => Outcome::Open,
+                    None => Outcome::Close,
+                };
+            }
+            _ => {}
+        }
+        Outcome::Open
+    }
+
+    pub fn render(&mut self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
+        let area = centered_rect(50, 50, area);
+        self.area = area;
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Block::default().style(Style::default().bg(theme::get().surface)),
+            area,
+        );
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(3)].as_ref())
+            .split(area);
+        f.render_widget(self.query.widget(), chunks[0]);
+
+        let items = self
+            .matches
+            .items
+            .iter()
+            .map(|a| {
+                ListItem::new(Spans::from(vec![
+                    Span::styled(
+                        format!("{:<28}", a.description()),
+                        Style::default().fg(theme::get().text),
+                    ),
+                    Span::styled(
+                        keymap::get().describe(*a),
+                        Style::default().fg(theme::get().muted),
+                    ),
+                ]))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(theme::get().accent)),
+            )
+            .highlight_style(theme::get().highlight());
+        self.matches.track(chunks[1]);
+        f.render_stateful_widget(list, chunks[1], &mut self.matches.state);
+    }
+}
@@ -1,19 +1,39 @@
-use std::io::StdoutLock;
+use std::{
+    io::Stdout,
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
 use tui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
-    text::{Spans, Text},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
+    style::Style,
+    text::{Span, Spans, Text},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
     Frame,
 };
-use tui_textarea::{Input, Key, TextArea};
+use tui_textarea::{CursorMove, Input, Key, TextArea};
+
+use crate::{
+    api::{Api, ApiError, PaymentType},
+    cli::parse_amount,
+    config,
+    keymap::{self, Action},
+    theme,
+    types::{format_cents, format_dollars, FundingInstrument, Person, StorySubType},
+};
+
+use super::{activate, bound, centered_rect, hit, home::StatefulList, inactivate, Page};
 
-use crate::{api::Api, types::FundingInstrument};
+// how many people to offer while the handle is empty, one per number key
+const QUICK_PICKS: usize = 9;
 
-use super::{activate, centered_rect, home::StatefulList, inactivate, Page};
+// how long typing has to pause before the handle is searched for
+const SEARCH_DELAY: Duration = Duration::from_millis(300);
+
+// shown when pay or request is pressed without a connection
+const OFFLINE_NOTICE: &str = "offline, nothing can be sent until venmo is reachable again";
 
 #[derive(Copy, Clone, PartialEq)]
 enum Field {
@@ -27,19 +47,77 @@ enum Field {
 
 struct PaymentSourcePopup {
     items: StatefulList<FundingInstrument>,
+    // e.g. the venmo balance not covering the amount
+    warning: Option<String>,
 }
 
 impl PaymentSourcePopup {
     fn new(sources: Vec<FundingInstrument>) -> PaymentSourcePopup {
         PaymentSourcePopup {
             items: StatefulList::with_items(sources),
+            warning: None,
         }
     }
 }
 
+// everything about a payment, resolved, for the user to check before it's sent
+struct Review {
+    person: Person,
+    amount_in_cents: u32,
+    note: String,
+    payment_type: PaymentType,
+    source: Option<FundingInstrument>,
+    fee_cents: i64,
+    // the daemon's confirmation token, when going through one
+    token: Option<String>,
+    // where the buttons were last drawn, for clicks
+    area: Rect,
+    confirm_area: Rect,
+    cancel_area: Rect,
+}
+
+impl Review {
+    fn lines(&self) -> Vec<(&'static str, String)> {
+        let friend = match self.person.is_friend {
+            Some(true) => "friends",
+            Some(false) => "not friends",
+            None => "unknown",
+        };
+        let mut lines = vec![
+            (
+                match self.payment_type {
+                    PaymentType::Pay => "Pay",
+                    PaymentType::Request => "Request from",
+                },
+                self.person.display_name.clone(),
+            ),
+            ("Handle", format!("@{} ({friend})", self.person.handle)),
+            ("Amount", format_dollars(self.amount_in_cents as i64)),
+            ("Note", self.note.clone()),
+            ("Audience", config::get().audience.to_string()),
+        ];
+        if let Some(source) = &self.source {
+            lines.push(("From", source.label()));
+            // venmo's eligibility check doesn't know which instrument was picked
+            lines.push((
+                "Est. fees",
+                if self.fee_cents == 0 {
+                    "none".to_string()
+                } else {
+                    format_dollars(self.fee_cents)
+                },
+            ));
+        }
+        lines
+    }
+}
+
 pub struct PayPage<'a> {
     selected: Field,
+    // resolve the recipient and fees, then show the review
+    waiting_for_review: bool,
     waiting_for_submit: bool,
+    loading_sources: bool,
     show_popup: bool,
     popup_items: Vec<ListItem<'a>>,
     amount: TextArea<'a>,
@@ -48,9 +126,58 @@ pub struct PayPage<'a> {
     send: Paragraph<'a>,
     recv: Paragraph<'a>,
     popup: PaymentSourcePopup,
+    review: Option<Review>,
+    // the person picked from the dropdown, so sending doesn't search again
+    recipient: Option<Person>,
+    suggestions: StatefulList<Person>,
+    // when to search for what's in the handle field
+    search_at: Option<Instant>,
+    // the suggestions are the quick picks rather than search results
+    showing_picks: bool,
+    // the quick picks were closed, so leave them closed until the handle changes
+    picks_closed: bool,
+    // where each field and button was last drawn, for clicks
+    areas: Vec<(Field, Rect)>,
+    notice: Option<String>,
+    failure: Option<String>,
     api: &'a mut Api,
 }
 
+// which source to start the popup on: the venmo balance if it covers the amount, otherwise the
+// default if it's free, otherwise a bank. a card with fees is only ever used if picked by hand
+fn preselect(
+    instruments: &[FundingInstrument],
+    amount_in_cents: Option<u32>,
+    default: Option<&str>,
+) -> Option<usize> {
+    let usable = |v: &FundingInstrument| v.peer_allowed() && !v.has_fees();
+    let amount = amount_in_cents.map(i64::from);
+    instruments
+        .iter()
+        .position(|v| {
+            usable(v) && v.is_balance() && amount.is_some_and(|a| v.balance_cents() >= Some(a))
+        })
+        .or_else(|| {
+            instruments
+                .iter()
+                .position(|v| usable(v) && !v.is_balance() && Some(v.id.as_str()) == default)
+        })
+        .or_else(|| instruments.iter().position(|v| usable(v) && v.is_bank()))
+}
+
+// stand-ins for an avatar, e.g. "JD" for Jane Doe
+fn initials(person: &Person) -> String {
+    let names = match (&person.first_name, &person.last_name) {
+        (Some(first), Some(last)) => vec![first.as_str(), last.as_str()],
+        _ => person.display_name.split_whitespace().take(2).collect(),
+    };
+    names
+        .iter()
+        .filter_map(|n| n.chars().next())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
 impl<'a> PayPage<'a> {
     pub fn new(api: &'a mut Api) -> Self {
         let mut v = Self {
@@ -61,99 +188,561 @@ impl<'a> PayPage<'a> {
             send: Paragraph::new(Text::from("Pay")).alignment(Alignment::Right),
             recv: Paragraph::new(Text::from("Request")).alignment(Alignment::Left),
             popup: PaymentSourcePopup::new(vec![]),
+            review: None,
+            recipient: None,
+            suggestions: StatefulList::with_items(vec![]),
+            search_at: None,
+            showing_picks: false,
+            picks_closed: false,
+            waiting_for_review: false,
             waiting_for_submit: false,
+            loading_sources: false,
             show_popup: false,
             popup_items: vec![],
             selected: Field::Unset,
+            areas: vec![],
+            notice: None,
+            failure: None,
         };
 
-        v.amount
-            .set_block(Block::default().borders(Borders::ALL).title("$"));
-        v.handle
-            .set_block(Block::default().borders(Borders::ALL).title("Username"));
-        v.note
-            .set_block(Block::default().borders(Borders::ALL).title("Note"));
+        v.clear();
+        v
+    }
 
-        inactivate(&mut v.amount);
-        inactivate(&mut v.handle);
-        inactivate(&mut v.note);
+    // back to an empty form, so a payment that was just sent isn't there to send again
+    fn clear(&mut self) {
+        for (input, title) in [
+            (&mut self.amount, "$"),
+            (&mut self.handle, "Username"),
+            (&mut self.note, "Note"),
+        ] {
+            *input = TextArea::default();
+            input.set_block(Block::default().borders(Borders::ALL).title(title));
+            inactivate(input);
+        }
+        self.send = Paragraph::new(Text::from("Pay")).alignment(Alignment::Right);
+        self.recv = Paragraph::new(Text::from("Request")).alignment(Alignment::Left);
+        self.selected = Field::Unset;
+        self.recipient = None;
+        self.suggestions = StatefulList::with_items(vec![]);
+        self.search_at = None;
+        self.showing_picks = false;
+        self.picks_closed = false;
+    }
 
-        v
+    // fill in a payment for the user to review, with the matching button focused
+    pub fn prefill(
+        &mut self,
+        handle: &str,
+        amount_cents: i64,
+        note: &str,
+        payment_type: PaymentType,
+    ) {
+        self.amount.insert_str(format_cents(amount_cents));
+        self.validate_amount();
+        self.handle.insert_str(handle);
+        self.note.insert_str(note);
+
+        inactivate(&mut self.amount);
+        inactivate(&mut self.handle);
+        inactivate(&mut self.note);
+
+        let highlighted = theme::get().highlight();
+        self.selected = match payment_type {
+            PaymentType::Pay => {
+                self.send = self.send.clone().style(highlighted);
+                Field::Pay
+            }
+            PaymentType::Request => {
+                self.recv = self.recv.clone().style(highlighted);
+                Field::Request
+            }
+        };
     }
 
     fn validate_amount(&mut self) {
-        if let Err(_) = self.amount.lines()[0].parse::<f64>() {
-            self.amount.set_style(Style::default().fg(Color::LightRed));
+        if self.amount.lines()[0].parse::<f64>().is_err() {
+            self.amount
+                .set_style(Style::default().fg(theme::get().error));
             self.amount.set_block(
                 Block::default()
                     .borders(Borders::ALL)
                     .title("ERROR: provided value must be a number"),
             );
         } else {
-            self.amount.set_style(Style::default().fg(Color::Blue));
+            self.amount
+                .set_style(Style::default().fg(theme::get().accent));
             self.amount
                 .set_block(Block::default().borders(Borders::ALL).title("$"));
         }
     }
 
+    fn set_handle_title(&mut self, title: String, error: bool) {
+        let style = if error {
+            Style::default().fg(theme::get().error)
+        } else {
+            Style::default()
+        };
+        self.handle.set_style(style);
+        self.handle
+            .set_block(Block::default().borders(Borders::ALL).title(title));
+        if self.selected == Field::Handle {
+            activate(&mut self.handle);
+        } else {
+            inactivate(&mut self.handle);
+        }
+    }
+
+    // search for what's been typed so far and offer the matches in the dropdown
     async fn validate_username(&mut self) {
-        if let Err(_) = self.api.fetch_user_id(&self.handle.lines()[0]).await {
-            self.handle.set_style(Style::default().fg(Color::LightRed));
-            self.handle.set_block(
+        self.search_at = None;
+        let query = self.handle.lines()[0]
+            .trim()
+            .trim_start_matches('@')
+            .to_string();
+        self.showing_picks = false;
+        if query.is_empty() {
+            self.suggestions = StatefulList::with_items(vec![]);
+            return;
+        }
+
+        let people = match self.api.search_people(&query).await {
+            Ok(people) if people.is_empty() => {
+                self.set_handle_title("ERROR: username not valid".to_string(), true);
+                vec![]
+            }
+            Ok(people) => {
+                self.set_handle_title("Username".to_string(), false);
+                people
+            }
+            Err(_) => {
+                self.set_handle_title("ERROR: search failed".to_string(), true);
+                vec![]
+            }
+        };
+        self.suggestions = StatefulList::with_items(people);
+    }
+
+    // pinned people first, then whoever we've paid or been paid by lately
+    fn quick_picks(&self) -> Vec<Person> {
+        let mut people = self.api.cache.favourites();
+        for story in self.api.cache.stories() {
+            if people.len() >= QUICK_PICKS {
+                break;
+            }
+            if story.title.payload.sub_type != StorySubType::P2p {
+                continue;
+            }
+            let Some(other) = story.counterparty() else {
+                continue;
+            };
+            if !people.iter().any(|p| p.id == other.id) {
+                people.push(other.into());
+            }
+        }
+        people.truncate(QUICK_PICKS);
+        people
+    }
+
+    fn show_quick_picks(&mut self) {
+        let selected = self.suggestions.state.selected();
+        self.suggestions = StatefulList::with_items(self.quick_picks());
+        if selected.is_some_and(|i| i < self.suggestions.items.len()) {
+            self.suggestions.state.select(selected);
+        }
+        self.showing_picks = true;
+    }
+
+    // pin or unpin whoever's highlighted in the dropdown, or the person already picked
+    async fn toggle_favourite(&mut self) {
+        let person = match self.suggestions.state.selected() {
+            Some(i) if self.dropdown_open() => self.suggestions.items.get(i).cloned(),
+            _ => self.recipient.clone(),
+        };
+        let Some(person) = person else {
+            return;
+        };
+
+        match self.api.toggle_favourite(&person).await {
+            Ok(true) => self.notice = Some(format!("pinned @{}", person.handle)),
+            Ok(false) => self.notice = Some(format!("unpinned @{}", person.handle)),
+            Err(e) => self.failure = Some(e.to_string()),
+        }
+        if self.showing_picks {
+            self.show_quick_picks();
+        }
+    }
+
+    // the handle changed, so whoever was picked no longer applies
+    fn handle_edited(&mut self) {
+        self.picks_closed = false;
+        if self.showing_picks {
+            self.suggestions = StatefulList::with_items(vec![]);
+            self.showing_picks = false;
+        }
+        if self.recipient.take().is_some() {
+            self.set_handle_title("Username".to_string(), false);
+        }
+        // nothing to search offline
+        if !self.api.offline {
+            self.search_at = Some(Instant::now() + SEARCH_DELAY);
+        }
+    }
+
+    fn dropdown_open(&self) -> bool {
+        self.selected == Field::Handle && !self.suggestions.items.is_empty()
+    }
+
+    // lock in a person from the dropdown and move on to the note
+    fn pick(&mut self, i: usize) {
+        let person = self.suggestions.items.swap_remove(i);
+        self.suggestions = StatefulList::with_items(vec![]);
+        self.search_at = None;
+
+        self.handle.move_cursor(CursorMove::End);
+        self.handle.delete_line_by_head();
+        self.handle.insert_str(&person.handle);
+        self.set_handle_title(format!("Username ({})", person.display_name), false);
+        self.recipient = Some(person);
+        self.focus(Field::Note);
+    }
+
+    fn render_dropdown(
+        &mut self,
+        f: &mut Frame<'_, CrosstermBackend<Stdout>>,
+        below: Rect,
+        area: Rect,
+    ) {
+        let y = below.bottom();
+        let height = (self.suggestions.items.len() as u16 + 2)
+            .min(QUICK_PICKS as u16 + 2)
+            .min(area.bottom().saturating_sub(y));
+        if height < 3 {
+            return;
+        }
+        let rect = Rect {
+            x: below.x,
+            y,
+            width: below.width,
+            height,
+        };
+
+        let pinned = if self.showing_picks {
+            self.api.cache.favourites()
+        } else {
+            vec![]
+        };
+        let items = self
+            .suggestions
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let mut spans = vec![];
+                if self.showing_picks {
+                    spans.push(Span::styled(
+                        format!("{} ", i + 1),
+                        Style::default().fg(theme::get().muted),
+                    ));
+                }
+                spans.extend([
+                    Span::styled(
+                        format!("{:<3}", initials(p)),
+                        Style::default().fg(theme::get().accent),
+                    ),
+                    Span::styled(
+                        p.display_name.clone(),
+                        Style::default().fg(theme::get().text),
+                    ),
+                    Span::styled(
+                        format!(" @{}", p.handle),
+                        Style::default().fg(theme::get().muted),
+                    ),
+                ]);
+                if pinned.iter().any(|f| f.id == p.id) {
+                    spans.push(Span::styled(
+                        " pinned",
+                        Style::default().fg(theme::get().accent),
+                    ));
+                }
+                if p.is_friend == Some(true) {
+                    spans.push(Span::styled(
+                        " friend",
+                        Style::default().fg(theme::get().positive),
+                    ));
+                }
+                ListItem::new(Spans::from(spans))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .title("ERROR: username not valid"),
-            );
-        } else {
-            inactivate(&mut self.handle);
+                    .title(if self.showing_picks {
+                        "Pinned & recent"
+                    } else {
+                        ""
+                    })
+                    .border_style(Style::default().fg(theme::get().accent))
+                    .style(Style::default().bg(theme::get().surface)),
+            )
+            .highlight_style(theme::get().highlight());
+
+        f.render_widget(Clear, rect);
+        self.suggestions.track(rect);
+        f.render_stateful_widget(list, rect, &mut self.suggestions.state);
+    }
+
+    // enter on a field moves to the next one, on a button reviews or picks a funding source
+    fn submit(&mut self) {
+        if self.selected == Field::Request {
+            if self.api.offline {
+                self.failure = Some(OFFLINE_NOTICE.to_string());
+            } else {
+                self.waiting_for_review = true;
+            }
+        }
+
+        // the sources are fetched in make_progress, then the popup opens
+        if self.selected == Field::Pay {
+            self.loading_sources = true;
+        }
+
+        self.selected = match self.selected {
+            Field::Amount => {
+                inactivate(&mut self.amount);
+                activate(&mut self.handle);
+                Field::Handle
+            }
+            Field::Handle => {
+                inactivate(&mut self.handle);
+                activate(&mut self.note);
+                Field::Note
+            }
+
+            f => f,
         }
     }
 
+    // whether the highlighted funding source can pay people
+    fn source_allowed(&self) -> bool {
+        self.popup
+            .items
+            .state
+            .selected()
+            .and_then(|i| self.popup.items.items.get(i))
+            .is_some_and(|v| v.peer_allowed())
+    }
+
+    // move focus straight to a field or button, e.g. on a click
+    fn focus(&mut self, field: Field) {
+        match self.selected {
+            Field::Amount => inactivate(&mut self.amount),
+            Field::Handle => inactivate(&mut self.handle),
+            Field::Note => inactivate(&mut self.note),
+            Field::Pay => self.send = self.send.clone().style(Style::default()),
+            Field::Request => self.recv = self.recv.clone().style(Style::default()),
+            Field::Unset => {}
+        }
+        match field {
+            Field::Amount => activate(&mut self.amount),
+            Field::Handle => activate(&mut self.handle),
+            Field::Note => activate(&mut self.note),
+            Field::Pay => self.send = self.send.clone().style(theme::get().highlight()),
+            Field::Request => self.recv = self.recv.clone().style(theme::get().highlight()),
+            Field::Unset => {}
+        }
+        self.selected = field;
+    }
+
     fn render_payment_source_popup(
         &mut self,
-        f: &mut Frame<'_, CrosstermBackend<StdoutLock<'_>>>,
+        f: &mut Frame<'_, CrosstermBackend<Stdout>>,
         area: Rect,
     ) {
-        let block = Block::default().style(Style::default().bg(Color::White));
+        let block = Block::default().style(Style::default().bg(theme::get().surface));
         let area = centered_rect(40, 60, area);
         f.render_widget(Clear, area); //this clears out the background
         f.render_widget(block, area);
+
+        let area = match &self.popup.warning {
+            Some(warning) => {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(3), Constraint::Length(1)].as_ref())
+                    .split(area);
+                f.render_widget(
+                    Paragraph::new(warning.as_str())
+                        .style(
+                            Style::default()
+                                .fg(theme::get().warning_fg)
+                                .bg(theme::get().warning_bg),
+                        )
+                        .alignment(Alignment::Center),
+                    chunks[1],
+                );
+                chunks[0]
+            }
+            None => area,
+        };
         let list = List::new(self.popup_items.clone())
             .block(
                 Block::default()
                     .borders(Borders::ALL)
                     .title("Funding Source")
-                    .border_style(Style::default().fg(Color::Blue)),
+                    .border_style(Style::default().fg(theme::get().accent)),
             )
-            .highlight_style(
-                Style::default()
-                    .fg(Color::Blue)
-                    .add_modifier(Modifier::BOLD),
-            );
+            .highlight_style(theme::get().highlight());
+        self.popup.items.track(area);
         f.render_stateful_widget(list, area, &mut self.popup.items.state);
     }
+
+    fn render_review(&mut self, f: &mut Frame<'_, CrosstermBackend<Stdout>>, area: Rect) {
+        let Some(review) = &mut self.review else {
+            return;
+        };
+        let area = centered_rect(50, 50, area);
+        f.render_widget(Clear, area);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title("Confirm")
+            .border_style(Style::default().fg(theme::get().accent))
+            .style(Style::default().bg(theme::get().surface));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+            .margin(1)
+            .split(inner);
+
+        let lines = review
+            .lines()
+            .into_iter()
+            .map(|(label, value)| {
+                Spans::from(vec![
+                    Span::styled(
+                        format!("{label:<14}"),
+                        Style::default().fg(theme::get().muted),
+                    ),
+                    Span::styled(value, Style::default().fg(theme::get().text)),
+                ])
+            })
+            .collect::<Vec<_>>();
+        f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), chunks[0]);
+
+        let buttons = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(chunks[1]);
+        f.render_widget(
+            Paragraph::new(format!(
+                "Confirm ({})",
+                keymap::get().describe(Action::Submit)
+            ))
+            .style(theme::get().highlight())
+            .alignment(Alignment::Center),
+            buttons[0],
+        );
+        f.render_widget(
+            Paragraph::new(format!("Cancel ({})", keymap::get().describe(Action::Back)))
+                .alignment(Alignment::Center),
+            buttons[1],
+        );
+
+        review.area = area;
+        review.confirm_area = buttons[0];
+        review.cancel_area = buttons[1];
+    }
 }
 
 #[async_trait]
 impl<'a> Page for PayPage<'a> {
     async fn on_input_event(&mut self, event: Input) -> bool {
+        let action = keymap::get().action(&event, self.typing());
+
+        // nothing is sent without an explicit yes
+        if self.review.is_some() {
+            match action {
+                Some(Action::Submit) => self.waiting_for_submit = true,
+                Some(Action::Back) => self.review = None,
+                _ => {}
+            }
+            return false;
+        }
+
+        if action == Some(Action::Pin) {
+            self.toggle_favourite().await;
+            return false;
+        }
+
+        if self.dropdown_open() {
+            // number keys pick straight from the quick picks
+            if let Input {
+                key: Key::Char(c @ '1'..='9'),
+                ctrl: false,
+                alt: false,
+            } = event
+            {
+                let i = c as usize - '1' as usize;
+                if self.showing_picks && i < self.suggestions.items.len() {
+                    self.pick(i);
+                    return false;
+                }
+            }
+
+            match action {
+                Some(Action::NextItem) => {
+                    self.suggestions.next();
+                    return false;
+                }
+                Some(Action::PrevItem) => {
+                    self.suggestions.previous();
+                    return false;
+                }
+                Some(Action::Submit) => {
+                    match self.suggestions.state.selected() {
+                        Some(i) => self.pick(i),
+                        // nothing highlighted, so go on with the typed handle as if none were shown
+                        None => {
+                            self.suggestions = StatefulList::with_items(vec![]);
+                            self.picks_closed = self.showing_picks;
+                            self.search_at = None;
+                            self.submit();
+                        }
+                    }
+                    return false;
+                }
+                Some(Action::Back) => {
+                    self.suggestions = StatefulList::with_items(vec![]);
+                    self.picks_closed = self.showing_picks;
+                    return false;
+                }
+                _ => {}
+            }
+        }
+
         if self.show_popup {
-            match event {
-                Input { key: Key::Down, .. } => {
+            match action {
+                Some(Action::NextItem) => {
                     self.popup.items.next();
                 }
-                Input { key: Key::Up, .. } => {
+                Some(Action::PrevItem) => {
                     self.popup.items.previous();
                 }
-                Input { key: Key::Esc, .. } => {
+                Some(Action::Back) => {
                     // reset selection if re-opened
                     self.popup.items.state.select(Some(0));
                     self.show_popup = false;
                 }
-                Input {
-                    key: Key::Enter, ..
-                } => {
-                    self.waiting_for_submit = true;
+                // sources are still listed offline, but nothing can be sent
+                Some(Action::Submit) if self.api.offline => {
+                    self.failure = Some(OFFLINE_NOTICE.to_string());
+                }
+                Some(Action::Submit) if self.source_allowed() => {
+                    self.waiting_for_review = true;
                 }
                 _ => {}
             }
@@ -161,8 +750,8 @@ impl<'a> Page for PayPage<'a> {
             return false;
         }
 
-        match event {
-            Input { key: Key::Down, .. } => {
+        match action {
+            Some(Action::NextItem) => {
                 self.selected = match self.selected {
                     Field::Amount => {
                         inactivate(&mut self.amount);
@@ -175,18 +764,14 @@ impl<'a> Page for PayPage<'a> {
                         Field::Note
                     }
                     Field::Note => {
-                        self.send = self.send.clone().style(
-                            Style::default()
-                                .fg(Color::Blue)
-                                .add_modifier(Modifier::BOLD),
-                        );
+                        self.send = self.send.clone().style(theme::get().highlight());
                         inactivate(&mut self.note);
                         Field::Pay
                     }
                     f => f,
                 };
             }
-            Input { key: Key::Up, .. } => {
+            Some(Action::PrevItem) => {
                 self.selected = match self.selected {
                     Field::Handle => {
                         inactivate(&mut self.handle);
@@ -211,15 +796,11 @@ impl<'a> Page for PayPage<'a> {
                     f => f,
                 }
             }
-            Input { key: Key::Left, .. } => {
+            Some(Action::Left) => {
                 self.selected = match self.selected {
                     Field::Request => {
                         self.recv = self.recv.clone().style(Style::default());
-                        self.send = self.send.clone().style(
-                            Style::default()
-                                .fg(Color::Blue)
-                                .add_modifier(Modifier::BOLD),
-                        );
+                        self.send = self.send.clone().style(theme::get().highlight());
                         Field::Pay
                     }
                     Field::Amount => {
@@ -241,76 +822,31 @@ impl<'a> Page for PayPage<'a> {
                     _ => Field::Unset,
                 }
             }
-            Input {
-                key: Key::Right, ..
-            } => {
+            Some(Action::Right) => {
                 self.selected = match self.selected {
                     Field::Pay => {
                         self.send = self.send.clone().style(Style::default());
-                        self.recv = self.recv.clone().style(
-                            Style::default()
-                                .fg(Color::Blue)
-                                .add_modifier(Modifier::BOLD),
-                        );
+                        self.recv = self.recv.clone().style(theme::get().highlight());
                         Field::Request
                     }
                     f => f,
                 }
             }
-            Input {
-                key: Key::Enter, ..
-            } => {
-                if self.selected == Field::Request && self.waiting_for_submit == false {
-                    self.waiting_for_submit = true;
-                }
-
-                if self.selected == Field::Pay {
-                    self.popup.items.items = self.api.get_funding_instruments().await.expect(":(");
-                    self.popup_items = self
-                        .popup
-                        .items
-                        .items
-                        .iter()
-                        .enumerate()
-                        .map(|(i, v)| {
-                            ListItem::new(Spans::from(format!(
-                                "{}. {} ({})",
-                                i + 1,
-                                v.name.clone(),
-                                v.instrument_type.clone()
-                            )))
-                            .style(Style::default().fg(Color::Black))
-                        })
-                        .collect();
-                    self.show_popup = true;
-                }
-
-                self.selected = match self.selected {
-                    Field::Amount => {
-                        inactivate(&mut self.amount);
-                        activate(&mut self.handle);
-                        Field::Handle
-                    }
-                    Field::Handle => {
-                        inactivate(&mut self.handle);
-                        activate(&mut self.note);
-                        Field::Note
-                    }
-
-                    f => f,
-                }
-            }
-            Input { key: Key::Esc, .. } => return true,
-            k => match self.selected {
+            Some(Action::Submit) => self.submit(),
+            Some(Action::Back) => return true,
+            _ => match self.selected {
                 Field::Amount => {
-                    self.amount.input(k.clone());
+                    self.amount.input(event);
                     self.validate_amount();
                 }
                 Field::Handle => {
-                    self.handle.input(k.clone());
+                    let edited = self.handle.input(event);
+                    if edited {
+                        self.handle_edited();
+                    }
                 }
                 Field::Note => {
-                    self.note.input(k.clone());
+                    self.note.input(event);
                 }
 
                 _ => {}
@@ -319,53 +855,348 @@ impl<'a> Page for PayPage<'a> {
 
         self.selected == Field::Unset
     }
-    async fn make_progress(&mut self) -> bool {
+
+    async fn on_mouse_event(&mut self, event: MouseEvent) {
+        if let Some(review) = &self.review {
+            if let MouseEventKind::Down(MouseButton::Left) = event.kind {
+                if hit(review.confirm_area, &event) {
+                    self.waiting_for_submit = true;
+                } else if hit(review.cancel_area, &event) || !hit(review.area, &event) {
+                    self.review = None;
+                }
+            }
+            return;
+        }
+
+        if self.show_popup {
+            match event.kind {
+                MouseEventKind::ScrollDown => self.popup.items.next(),
+                MouseEventKind::ScrollUp => self.popup.items.previous(),
+                MouseEventKind::Down(MouseButton::Left) => match self.popup.items.item_at(&event) {
+                    // a second click on the same source goes on to the review
+                    Some(i) if self.popup.items.state.selected() == Some(i) => {
+                        if self.api.offline {
+                            self.failure = Some(OFFLINE_NOTICE.to_string());
+                        } else if self.source_allowed() {
+                            self.waiting_for_review = true;
+                        }
+                    }
+                    Some(i) => self.popup.items.state.select(Some(i)),
+                    // clicking away closes it, like esc
+                    None => {
+                        self.popup.items.state.select(Some(0));
+                        self.show_popup = false;
+                    }
+                },
+                _ => {}
+            }
+            return;
+        }
+
+        if self.dropdown_open() {
+            match event.kind {
+                MouseEventKind::ScrollDown => {
+                    self.suggestions.next();
+                    return;
+                }
+                MouseEventKind::ScrollUp => {
+                    self.suggestions.previous();
+                    return;
+                }
+                MouseEventKind::Down(MouseButton::Left) => {
+                    if let Some(i) = self.suggestions.item_at(&event) {
+                        self.pick(i);
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let MouseEventKind::Down(MouseButton::Left) = event.kind {
+            let Some(&(field, _)) = self.areas.iter().find(|(_, area)| hit(*area, &event)) else {
+                return;
+            };
+            self.focus(field);
+            if matches!(field, Field::Pay | Field::Request) {
+                self.submit();
+            }
+        }
+    }
+
+    fn help(&self) -> Vec<(String, &'static str)> {
+        if self.review.is_some() {
+            return vec![
+                bound(Action::Submit, "Send it"),
+                bound(Action::Back, "Cancel"),
+            ];
+        }
+        if self.dropdown_open() {
+            let mut lines = vec![
+                bound(Action::NextItem, "Next match"),
+                bound(Action::PrevItem, "Previous match"),
+                bound(Action::Submit, "Pay this person"),
+                bound(Action::Pin, "Pin or unpin this person"),
+                bound(Action::Back, "Close the matches"),
+            ];
+            if self.showing_picks {
+                lines.insert(3, ("1-9".to_string(), "Pay the person with that number"));
+            }
+            return lines;
+        }
+        if self.show_popup {
+            return vec![
+                bound(Action::NextItem, "Next funding source"),
+                bound(Action::PrevItem, "Previous funding source"),
+                bound(Action::Submit, "Review, paying with this source"),
+                bound(Action::Back, "Cancel"),
+            ];
+        }
+        vec![
+            bound(Action::NextItem, "Next field"),
+            bound(Action::PrevItem, "Previous field"),
+            bound(Action::Right, "Request instead of pay"),
+            bound(
+                Action::Left,
+                "Pay instead of request, or back to the sidebar",
+            ),
+            bound(Action::Submit, "Next field, or review and send"),
+            bound(Action::Pin, "Pin or unpin the picked person"),
+            bound(Action::Back, "Back to the sidebar"),
+        ]
+    }
+
+    // the form is always on screen, so there's nothing to open
+    fn on_action(&mut self, action: Action) -> bool {
+        action == Action::Pay
+    }
+
+    fn typing(&self) -> bool {
+        !self.show_popup
+            && self.review.is_none()
+            && matches!(self.selected, Field::Amount | Field::Handle | Field::Note)
+    }
+    async fn make_progress(&mut self) -> Result<bool, ApiError> {
         if self.selected == Field::Unset {
             self.selected = Field::Amount;
             activate(&mut self.amount);
         }
 
-        if self.waiting_for_submit {
-            let amount_in_cents = (self.amount.lines()[0]
-                .parse::<f32>()
-                .expect("failed to parse to u32")
-                * 100.0) as u32;
+        if self.search_at.is_some_and(|t| t <= Instant::now()) {
+            self.validate_username().await;
+            return Ok(true);
+        }
 
-            let user_id = self
+        // offer the usual people while the handle is empty
+        if self.selected == Field::Handle
+            && self.handle.lines()[0].is_empty()
+            && !self.showing_picks
+            && !self.picks_closed
+            && self.search_at.is_none()
+        {
+            self.show_quick_picks();
+            return Ok(true);
+        }
+
+        if self.loading_sources {
+            // the wallet's cache, unless a payment or a first run left nothing there
+            self.popup.items.items = match self.api.cache.funding_instruments() {
+                Some(instruments) => instruments,
+                None => self.api.get_funding_instruments().await?,
+            };
+            self.loading_sources = false;
+            // an empty popup would only offer nothing to pick
+            if self.popup.items.items.is_empty() && self.api.offline {
+                self.failure = Some("offline, and no funding sources are cached yet".to_string());
+                return Ok(true);
+            }
+            let amount_in_cents = parse_amount(&self.amount.lines()[0]).ok();
+            self.popup_items = self
+                .popup
+                .items
+                .items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let mut text = format!("{}. {}", i + 1, v.label());
+                    let fee = amount_in_cents.map_or(0, |a| v.fee_cents(a));
+                    if fee > 0 {
+                        text.push_str(&format!("  +{} fee", format_dollars(fee)));
+                    }
+                    // still listed, so it's clear why it can't be picked
+                    if !v.peer_allowed() {
+                        text.push_str("  (not for paying people)");
+                    }
+                    let color = if v.peer_allowed() {
+                        theme::get().text
+                    } else {
+                        theme::get().muted
+                    };
+                    ListItem::new(Spans::from(text)).style(Style::default().fg(color))
+                })
+                .collect();
+
+            let default = self.api.default_funding_source();
+            let items = &self.popup.items.items;
+            self.popup
+                .items
+                .state
+                .select(preselect(items, amount_in_cents, default.as_deref()));
+
+            // say so when the balance falls short, rather than quietly using the bank
+            self.popup.warning = match (
+                items
+                    .iter()
+                    .find_map(|v| v.balance_cents().filter(|_| v.is_balance())),
+                amount_in_cents,
+            ) {
+                (Some(balance), Some(amount)) if balance < amount as i64 => Some(format!(
+                    "venmo balance ({}) doesn't cover {}",
+                    format_dollars(balance),
+                    format_dollars(amount as i64)
+                )),
+                _ => None,
+            };
+            self.show_popup = true;
+            return Ok(true);
+        }
+
+        if self.waiting_for_review {
+            let amount_in_cents = match parse_amount(&self.amount.lines()[0]) {
+                Ok(cents) => cents,
+                // nothing to retry until the amount is fixed
+                Err(e) => {
+                    self.cancel();
+                    return Err(ApiError::PaymentSendFailure(e));
+                }
+            };
+            let payment_type = if self.selected == Field::Request {
+                PaymentType::Request
+            } else {
+                PaymentType::Pay
+            };
+            let source = match payment_type {
+                PaymentType::Pay => match self.popup.items.state.selected() {
+                    Some(i) if i < self.popup.items.items.len() => {
+                        Some(self.popup.items.items[i].clone())
+                    }
+                    _ => {
+                        self.cancel();
+                        return Err(ApiError::PaymentSendFailure(
+                            "no funding source picked.".to_string(),
+                        ));
+                    }
+                },
+                PaymentType::Request => None,
+            };
+
+            let note = self.note.lines()[0].clone();
+            let person = match &self.recipient {
+                Some(person) => person.clone(),
+                // typed or filled in without picking anyone, so it has to be exact
+                None => self.api.find_person(&self.handle.lines()[0]).await?,
+            };
+            let fee_cents = match payment_type {
+                PaymentType::Pay => {
+                    let eligibility = self
+                        .api
+                        .fetch_eligibility(amount_in_cents, &note, &person.id)
+                        .await?;
+                    if !eligibility.eligible {
+                        self.cancel();
+                        return Err(ApiError::PaymentSendFailure(
+                            "not eligible to send this.".to_string(),
+                        ));
+                    }
+                    // venmo's fees for the payment if it gave any, otherwise the instrument's rates.
+                    // either way it's only an estimate for the picked instrument
+                    match (eligibility.total_fees(), &source) {
+                        (0, Some(source)) => source.fee_cents(amount_in_cents),
+                        (fees, _) => fees,
+                    }
+                }
+                PaymentType::Request => 0,
+            };
+
+            let token = self
                 .api
-                .fetch_user_id(&self.handle.lines()[0])
-                .await
-                .expect("don't fail rn");
+                .prepare_payment(
+                    amount_in_cents,
+                    &note,
+                    &person,
+                    payment_type,
+                    source.as_ref().map(|s| s.id.as_str()),
+                )
+                .await?;
 
+            self.waiting_for_review = false;
+            self.review = Some(Review {
+                person,
+                amount_in_cents,
+                note,
+                payment_type,
+                source,
+                fee_cents,
+                token,
+                area: Rect::default(),
+                confirm_area: Rect::default(),
+                cancel_area: Rect::default(),
+            });
+            return Ok(true);
+        }
+
+        if self.waiting_for_submit {
+            let Some(review) = &self.review else {
+                self.waiting_for_submit = false;
+                return Ok(false);
+            };
             self.api
                 .submit_payment(
-                    amount_in_cents,
-                    &self.note.lines()[0],
-                    &user_id,
-                    match self.selected {
-                        Field::Pay => crate::api::PaymentType::Pay,
-                        Field::Request => crate::api::PaymentType::Request,
-                        _ => panic!("not possible!"),
-                    },
-                    match self.selected {
-                        Field::Pay => Some(
-                            &self.popup.items.items[self.popup.items.state.selected().unwrap()].id,
-                        ),
-                        Field::Request => None,
-                        _ => panic!("not possible@"),
-                    },
+                    review.amount_in_cents,
+                    &review.note,
+                    &review.person,
+                    review.payment_type,
+                    review.source.as_ref().map(|s| s.id.as_str()),
+                    review.token.as_deref(),
                 )
-                .await
-                .expect("don't fail rn");
+                .await?;
 
+            let amount = format_dollars(review.amount_in_cents as i64);
+            let handle = &review.person.handle;
+            self.notice = Some(match review.payment_type {
+                PaymentType::Pay => format!("paid @{handle} {amount}"),
+                PaymentType::Request => format!("requested {amount} from @{handle}"),
+            });
             self.waiting_for_submit = false;
-            return true;
+            self.review = None;
+            self.show_popup = false;
+            self.clear();
+            return Ok(true);
         }
 
-        false
+        Ok(false)
+    }
+
+    fn wake_in(&self) -> Option<Duration> {
+        self.search_at
+            .map(|t| t.saturating_duration_since(Instant::now()))
+    }
+
+    fn cancel(&mut self) {
+        self.loading_sources = false;
+        self.waiting_for_review = false;
+        self.waiting_for_submit = false;
+    }
+
+    fn notice(&mut self) -> Option<String> {
+        self.notice.take()
     }
 
-    fn render(&mut self, f: &mut Frame<CrosstermBackend<StdoutLock>>, area: Rect) {
+    fn failure(&mut self) -> Option<String> {
+        self.failure.take()
+    }
+
+    fn render(&mut self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints(
@@ -379,6 +1210,7 @@ impl<'a> Page for PayPage<'a> {
             )
             .margin(2)
             .split(area);
+        let handle_area = chunks[1];
 
         {
             let a_widget = self.amount.widget();
@@ -402,15 +1234,31 @@ impl<'a> Page for PayPage<'a> {
 
             f.render_widget(self.send.clone(), btn_layout[0]);
             f.render_widget(self.recv.clone(), btn_layout[2]);
+
+            self.areas = vec![
+                (Field::Amount, chunks[0]),
+                (Field::Handle, chunks[1]),
+                (Field::Note, chunks[2]),
+                (Field::Pay, btn_layout[0]),
+                (Field::Request, btn_layout[2]),
+            ];
         }
 
         let block = Block::default()
-            .title("Pay & Request")
+            .title(if self.api.offline {
+                "Pay & Request (offline)"
+            } else {
+                "Pay & Request"
+            })
             .borders(Borders::ALL);
         f.render_widget(block, area);
 
+        if self.dropdown_open() {
+            self.render_dropdown(f, handle_area, area);
+        }
         if self.show_popup {
             self.render_payment_source_popup(f, area);
         }
+        self.render_review(f, area);
     }
 }
@@ -1,11 +1,10 @@
-use tui::{
-    style::Color,
-    widgets::{
-        canvas::{Canvas, Context, Painter, Shape},
-        Block, Borders,
-    },
+use tui::widgets::{
+    canvas::{Canvas, Context, Painter, Shape},
+    Block, Borders,
 };
 
+use crate::theme;
+
 struct QrCode {
     data: Vec<Vec<bool>>,
 }
@@ -14,10 +13,12 @@ impl Shape for QrCode {
     fn draw(&self, painter: &mut Painter) {
         for (y, v) in self.data.iter().enumerate() {
             for (x, b) in v.iter().enumerate() {
+                let theme = theme::get();
                 if *b {
-                    painter.paint(x, y, Color::LightBlue);
-                } else {
-                    painter.paint(x, y, Color::White);
+                    painter.paint(x, y, theme.qr_dark);
+                } else if !theme.plain {
+                    // without colors, blank space has to stand in for the light modules
+                    painter.paint(x, y, theme.qr_light);
                 }
             }
         }
@@ -34,7 +35,7 @@ trait AddMargin {
 impl AddMargin for QrCode {
     fn add_margin(&mut self) {
         let mut new_size = (self.data.len() as f64 * 1.1) as usize;
-        if new_size % 2 != 0 {
+        if !new_size.is_multiple_of(2) {
             new_size += 1;
         }
 
@@ -84,7 +85,7 @@ impl Double for QrCode {
     }
 }
 
-pub fn generate(input: &str) -> Canvas<impl Fn(&mut Context)> {
+pub fn generate(input: &str) -> Canvas<'_, impl Fn(&mut Context)> {
     let data = Box::leak(Box::new(
         qrcode_generator::to_matrix(input, qrcode_generator::QrCodeEcc::Low).unwrap(),
     ));
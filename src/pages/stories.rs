@@ -1,41 +1,72 @@
-use std::io::StdoutLock;
+use std::{fs::File, io::Stdout};
 
 use async_trait::async_trait;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
 use tui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Rect},
-    style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Cell, Row, Table, TableState},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListItem, Paragraph, Row, Table, TableState, Wrap,
+    },
     Frame,
 };
-use tui_textarea::{Input, Key};
+use tui_textarea::{CursorMove, Input, TextArea};
 
 use crate::{
-    api::Api,
-    types::{Story, StorySubType},
+    api::{Api, ApiError, PaymentType},
+    config,
+    export::{default_file_name, export, Format},
+    keymap::{self, Action, PAGE_STEP},
+    search::Query,
+    theme,
+    types::{format_dollars, SenderReciever, Story, StorySubType},
 };
 
-use super::Page;
+use super::{
+    activate, bound, centered_rect, copy_to_clipboard, first_visible, hit, home::StatefulList,
+    inactivate, row_at, Navigate, Page,
+};
 
 pub struct StoriesPage<'a> {
+    syncing: bool,
     loading: bool,
-    last: Option<String>,
+    searching: bool,
+    exporting: bool,
+    status: Option<String>,
+    notice: Option<String>,
+    failure: Option<String>,
+    export_formats: StatefulList<Format>,
     api: &'a mut Api,
     state: TableState,
+    stories: Vec<Story>,
+    query: Query,
+    search: TextArea<'a>,
     items: Vec<Vec<String>>,
+    // the story behind each row of `items`, footer aside
+    shown: Vec<Story>,
+    // the story opened with enter, and where its pane was last drawn
+    detail: Option<Story>,
+    detail_area: Rect,
+    navigate: Option<Navigate>,
+    // where the table was last drawn and its first visible row, for clicks
+    table_area: Rect,
+    offset: usize,
 }
 
-const LOAD_SIZE: u32 = 30;
-
 impl<'a> StoriesPage<'a> {
     pub fn create_table_rows(data: Vec<Story>) -> Vec<Vec<String>> {
         data.into_iter()
             .filter_map(|story| match story.title.payload.sub_type {
                 StorySubType::P2p => Some(vec![
-                    story.amount,
-                    story.title.receiver.unwrap().username,
-                    story.title.sender.unwrap().username,
-                    story.date,
+                    story.amount.clone(),
+                    story.title.receiver.clone().unwrap().username,
+                    story.title.sender.clone().unwrap().username,
+                    story
+                        .local_time()
+                        .map(|t| t.format(&config::get().date_format).to_string())
+                        .unwrap_or_else(|| story.date.clone()),
                     story.note.content.unwrap_or_default(),
                 ]),
                 _ => None,
@@ -43,38 +74,300 @@ impl<'a> StoriesPage<'a> {
             .collect::<Vec<_>>()
     }
 
-    pub fn new(api: &mut Api) -> StoriesPage {
-        StoriesPage {
-            last: None,
+    pub fn new(api: &mut Api) -> StoriesPage<'_> {
+        let mut search = TextArea::default();
+        search.set_block(Block::default().borders(Borders::ALL).title("Search"));
+        inactivate(&mut search);
+
+        // show whatever we already have while syncing newer stories
+        let mut page = StoriesPage {
+            syncing: !api.offline,
+            loading: false,
+            searching: false,
+            exporting: false,
+            status: None,
+            notice: None,
+            failure: None,
+            export_formats: StatefulList::with_items(Format::ALL.to_vec()),
+            stories: api.cache.stories(),
             api,
-            loading: true,
             state: TableState::default(),
-            items: vec![vec!["Loading...".to_string()]],
+            query: Query::default(),
+            search,
+            items: vec![],
+            shown: vec![],
+            detail: None,
+            detail_area: Rect::default(),
+            navigate: None,
+            table_area: Rect::default(),
+            offset: 0,
+        };
+        page.refresh_items();
+        page
+    }
+
+    // open with the search already filled in
+    pub fn with_query<'b>(api: &'b mut Api, query: &str) -> StoriesPage<'b> {
+        let mut page = Self::new(api);
+        page.search = TextArea::from([query]);
+        page.search.move_cursor(CursorMove::End);
+        page.update_query();
+        inactivate(&mut page.search);
+        page
+    }
+
+    // the trailing row, used to page further back
+    fn footer(&self) -> Option<&'static str> {
+        if self.api.offline {
+            None
+        } else if self.loading || (self.syncing && self.stories.is_empty()) {
+            Some("Loading...")
+        } else if self.syncing {
+            Some("Syncing...")
+        } else {
+            Some("Load more :)")
         }
     }
 
-    pub async fn load_more_items(&mut self) {
-        // load more items
-        let stories_data = self
-            .api
-            .get_recents(LOAD_SIZE, self.last.as_deref())
-            .await
-            .expect("failed to get story data");
+    fn refresh_items(&mut self) {
+        self.shown = self
+            .stories
+            .iter()
+            .filter(|s| self.query.matches(s) && s.title.payload.sub_type == StorySubType::P2p)
+            .cloned()
+            .collect();
+        self.items = Self::create_table_rows(self.shown.clone());
 
-        self.items.pop();
+        if let Some(footer) = self.footer() {
+            self.items.push(vec![footer.to_string()]);
+        }
 
-        self.items
-            .append(&mut Self::create_table_rows(stories_data.stories));
+        if let Some(i) = self.state.selected() {
+            if i >= self.items.len() {
+                self.state.select(self.items.len().checked_sub(1));
+            }
+        }
+    }
+
+    fn update_query(&mut self) {
+        match Query::parse(&self.search.lines()[0]) {
+            Ok(query) => {
+                self.query = query;
+                self.search.set_style(Style::default());
+                self.search
+                    .set_block(Block::default().borders(Borders::ALL).title("Search"));
+                activate(&mut self.search);
+                self.refresh_items();
+            }
+            Err(e) => {
+                self.search
+                    .set_style(Style::default().fg(theme::get().error));
+                self.search.set_block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!("ERROR: {e}")),
+                );
+            }
+        }
+    }
+
+    fn clear_search(&mut self) {
+        self.search = TextArea::default();
+        self.search
+            .set_block(Block::default().borders(Borders::ALL).title("Search"));
+        inactivate(&mut self.search);
+        self.query = Query::default();
+        self.refresh_items();
+    }
+
+    // write the stories matching the current search (of every type, not just the
+    // payments shown in the table) to a file in the working directory
+    fn export_items(&mut self, format: Format) {
+        let stories = self
+            .stories
+            .iter()
+            .filter(|s| self.query.matches(s))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let path = default_file_name(format);
+        let result = File::create(&path).and_then(|mut file| {
+            export(
+                &mut file,
+                format,
+                &stories,
+                self.api.identity.as_ref().unwrap(),
+            )
+        });
+
+        match result {
+            Ok(_) => self.notice = Some(format!("exported {} stories to {path}", stories.len())),
+            Err(e) => self.status = Some(format!("export failed! {e}")),
+        }
+    }
+
+    fn render_export_popup(&mut self, f: &mut Frame<'_, CrosstermBackend<Stdout>>, area: Rect) {
+        let area = centered_rect(30, 40, area);
+        f.render_widget(Clear, area);
+        let items = self
+            .export_formats
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| ListItem::new(Spans::from(format!("{}. {}", i + 1, v))))
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Export as")
+                    .border_style(Style::default().fg(theme::get().accent)),
+            )
+            .highlight_style(theme::get().highlight());
+        self.export_formats.track(area);
+        f.render_stateful_widget(list, area, &mut self.export_formats.state);
+    }
+
+    // fill in the pay form from the open story: `Pay` pays them back, `Request` asks again
+    fn pay_again(&mut self, payment_type: PaymentType) {
+        let Some(story) = &self.detail else {
+            return;
+        };
+        let (Some(person), Some(cents)) = (story.counterparty(), story.amount_cents()) else {
+            return;
+        };
+        self.navigate = Some(Navigate::Pay {
+            handle: person.username.clone(),
+            amount_cents: cents.abs(),
+            note: story.note.content.clone().unwrap_or_default(),
+            payment_type,
+        });
+        self.detail = None;
+    }
+
+    fn copy_id(&mut self) {
+        let Some(story) = &self.detail else {
+            return;
+        };
+        match copy_to_clipboard(&story.id) {
+            Ok(_) => self.notice = Some(format!("copied {}", story.id)),
+            Err(e) => self.failure = Some(format!("copy failed! {e}")),
+        }
+    }
+
+    fn render_detail(&mut self, f: &mut Frame<'_, CrosstermBackend<Stdout>>, area: Rect) {
+        let Some(story) = &self.detail else {
+            return;
+        };
+        let area = centered_rect(60, 70, area);
+        self.detail_area = area;
+        f.render_widget(Clear, area);
+
+        let line = |label: &str, value: String| {
+            Spans::from(vec![
+                Span::styled(
+                    format!("{label:<10}"),
+                    Style::default().fg(theme::get().muted),
+                ),
+                Span::styled(value, Style::default().fg(theme::get().text)),
+            ])
+        };
+        let party = |p: Option<&SenderReciever>| match p {
+            Some(p) => format!("{} (@{})", p.display_name, p.username),
+            None => "unknown".to_string(),
+        };
+        let known = |v: Option<&str>| v.unwrap_or("unknown").to_string();
+
+        let cents = story.amount_cents().unwrap_or_default();
+        let mut kind = story.title.payload.sub_type.as_str().to_string();
+        if let Some(action) = &story.title.payload.action {
+            kind.push_str(&format!(" ({action})"));
+        }
+        let funding = story
+            .funding_source
+            .as_ref()
+            .map(|s| match (&s.name, &s.source_type) {
+                (Some(name), Some(kind)) => format!("{name} ({kind})"),
+                (Some(name), None) => name.clone(),
+                (None, Some(kind)) => kind.clone(),
+                (None, None) => "unknown".to_string(),
+            });
 
-        self.items.push(vec!["Load more :)".to_string()]);
+        let mut lines = vec![
+            Spans::from(Span::styled(
+                story.amount.clone(),
+                Style::default()
+                    .fg(theme::get().amount(cents))
+                    .add_modifier(Modifier::BOLD),
+            )),
+            Spans::from(""),
+            line("From", party(story.title.sender.as_ref())),
+            line("To", party(story.title.receiver.as_ref())),
+            line(
+                "When",
+                story
+                    .local_time()
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S %Z").to_string())
+                    .unwrap_or_else(|| story.date.clone()),
+            ),
+            line("Id", story.id.clone()),
+            line("Type", kind),
+            line("Audience", known(story.audience.as_deref())),
+            line("Status", known(story.status.as_deref())),
+            line("Funding", known(funding.as_deref())),
+            Spans::from(""),
+        ];
+        lines.extend(
+            story
+                .note
+                .content
+                .as_deref()
+                .unwrap_or_default()
+                .lines()
+                .map(|l| Spans::from(l.to_string())),
+        );
+        lines.push(Spans::from(""));
+        lines.push(Spans::from(Span::styled(
+            format!(
+                "{}: copy id   {}: pay back {}   {}: request again {}   {}: close",
+                keymap::get().describe(Action::CopyId),
+                keymap::get().describe(Action::PayBack),
+                format_dollars(cents.abs()),
+                keymap::get().describe(Action::RequestAgain),
+                format_dollars(cents.abs()),
+                keymap::get().describe(Action::Back)
+            ),
+            Style::default().fg(theme::get().muted),
+        )));
 
-        self.last = Some(stories_data.next_id);
+        f.render_widget(
+            Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Transaction")
+                    .border_style(Style::default().fg(theme::get().accent))
+                    .style(Style::default().bg(theme::get().surface)),
+            ),
+            area,
+        );
+    }
+
+    pub async fn sync_items(&mut self) -> Result<(), ApiError> {
+        self.api.sync_stories(config::get().page_size).await?;
+        self.stories = self.api.cache.stories();
+        Ok(())
+    }
+
+    pub async fn load_more_items(&mut self) -> Result<(), ApiError> {
+        self.api.load_older_stories(config::get().page_size).await?;
+        self.stories = self.api.cache.stories();
+        Ok(())
     }
 
     pub fn next(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
-                if i >= self.items.len() - 1 {
+                if i >= self.items.len().saturating_sub(1) {
                     i
                 } else {
                     i + 1
@@ -99,6 +392,32 @@ impl<'a> StoriesPage<'a> {
         self.state.select(Some(i));
     }
 
+    // move n rows down without wrapping
+    pub fn forward(&mut self, n: usize) {
+        let i = self.state.selected().map_or(0, |i| i + n);
+        self.state
+            .select(Some(i.min(self.items.len().saturating_sub(1))));
+    }
+
+    pub fn back(&mut self, n: usize) {
+        let i = self.state.selected().map_or(0, |i| i.saturating_sub(n));
+        self.state.select(Some(i));
+    }
+
+    // a row is as tall as its longest cell
+    fn row_heights(&self) -> Vec<u16> {
+        self.items
+            .iter()
+            .map(|item| {
+                item.iter()
+                    .map(|content| content.chars().filter(|c| *c == '\n').count())
+                    .max()
+                    .unwrap_or(0) as u16
+                    + 1
+            })
+            .collect()
+    }
+
     pub fn unselect(&mut self) {
         self.state.select(None);
     }
@@ -106,22 +425,78 @@ impl<'a> StoriesPage<'a> {
 
 #[async_trait]
 impl<'a> Page for StoriesPage<'a> {
-    async fn on_input_event(&mut self, event: tui_textarea::Input) -> bool {
-        match event {
-            Input { key: Key::Down, .. } => self.next(),
-            Input { key: Key::Up, .. } => self.previous(),
-            Input {
-                key: Key::Enter, ..
-            } => {
+    async fn on_input_event(&mut self, event: Input) -> bool {
+        let action = keymap::get().action(&event, self.typing());
+
+        if self.exporting {
+            match action {
+                Some(Action::NextItem) => self.export_formats.next(),
+                Some(Action::PrevItem) => self.export_formats.previous(),
+                Some(Action::Submit) => {
+                    if let Some(i) = self.export_formats.state.selected() {
+                        self.export_items(self.export_formats.items[i]);
+                    }
+                    self.exporting = false;
+                }
+                Some(Action::Back) => self.exporting = false,
+                _ => {}
+            }
+            return false;
+        }
+
+        if self.detail.is_some() {
+            match action {
+                Some(Action::Back) | Some(Action::Left) => self.detail = None,
+                _ => match keymap::get().on_page(&event, Action::DETAIL) {
+                    Some(Action::CopyId) => self.copy_id(),
+                    Some(Action::PayBack) => self.pay_again(PaymentType::Pay),
+                    Some(Action::RequestAgain) => self.pay_again(PaymentType::Request),
+                    _ => {}
+                },
+            }
+            return false;
+        }
+
+        if self.searching {
+            match action {
+                Some(Action::Submit) => {
+                    self.searching = false;
+                    inactivate(&mut self.search);
+                }
+                Some(Action::Back) => {
+                    self.searching = false;
+                    self.clear_search();
+                }
+                _ => {
+                    if self.search.input(event) {
+                        self.update_query();
+                    }
+                }
+            }
+            return false;
+        }
+
+        match action {
+            Some(action @ (Action::Search | Action::Export)) => {
+                self.on_action(action);
+            }
+            Some(Action::NextItem) => self.next(),
+            Some(Action::PrevItem) => self.previous(),
+            Some(Action::FirstItem) => self.state.select(Some(0)),
+            Some(Action::LastItem) => self.forward(self.items.len()),
+            Some(Action::PageDown) => self.forward(PAGE_STEP),
+            Some(Action::PageUp) => self.back(PAGE_STEP),
+            Some(Action::Submit) => {
                 if let Some(i) = self.state.selected() {
-                    let len = self.items.len();
-                    if i >= len - 1 {
+                    if let Some(story) = self.shown.get(i) {
+                        self.detail = Some(story.clone());
+                    } else if !self.syncing && !self.api.offline {
                         self.loading = true;
-                        self.items[len - 1][0] = "Loading...".to_string();
+                        self.refresh_items();
                     }
                 }
             }
-            Input { key: Key::Left, .. } | Input { key: Key::Esc, .. } => {
+            Some(Action::Left) | Some(Action::Back) => {
                 self.unselect();
                 return true;
             }
@@ -130,21 +505,168 @@ impl<'a> Page for StoriesPage<'a> {
         false
     }
 
-    async fn make_progress(&mut self) -> bool {
+    async fn on_mouse_event(&mut self, event: MouseEvent) {
+        // clicking away closes the detail pane
+        if self.detail.is_some() {
+            if let MouseEventKind::Down(MouseButton::Left) = event.kind {
+                if !hit(self.detail_area, &event) {
+                    self.detail = None;
+                }
+            }
+            return;
+        }
+
+        if self.exporting {
+            if let MouseEventKind::Down(MouseButton::Left) = event.kind {
+                match self.export_formats.item_at(&event) {
+                    // a second click on the same format exports it
+                    Some(i) if self.export_formats.state.selected() == Some(i) => {
+                        self.export_items(self.export_formats.items[i]);
+                        self.exporting = false;
+                    }
+                    Some(i) => self.export_formats.state.select(Some(i)),
+                    None => self.exporting = false,
+                }
+            }
+            return;
+        }
+
+        match event.kind {
+            MouseEventKind::ScrollDown => self.next(),
+            MouseEventKind::ScrollUp => self.previous(),
+            MouseEventKind::Down(MouseButton::Left) if hit(self.table_area, &event) => {
+                let Some(y) = event.row.checked_sub(self.table_area.y + 3) else {
+                    return;
+                };
+                if let Some(i) = row_at(&self.row_heights(), 1, self.offset, y) {
+                    self.state.select(Some(i));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn typing(&self) -> bool {
+        self.searching
+    }
+
+    fn help(&self) -> Vec<(String, &'static str)> {
+        if self.detail.is_some() {
+            return vec![
+                bound(Action::CopyId, "Copy the id"),
+                bound(Action::PayBack, "Pay the same amount to them"),
+                bound(Action::RequestAgain, "Request the same amount from them"),
+                bound(Action::Back, "Close"),
+            ];
+        }
+        if self.exporting {
+            return vec![
+                bound(Action::NextItem, "Next format"),
+                bound(Action::PrevItem, "Previous format"),
+                bound(Action::Submit, "Export"),
+                bound(Action::Back, "Cancel"),
+            ];
+        }
+        if self.searching {
+            return vec![
+                (
+                    "from: to: with:".to_string(),
+                    "Match a person (@handle exactly)",
+                ),
+                ("amount:>20 amount:10..50".to_string(), "Match an amount"),
+                ("after: before:".to_string(), "Match a date (YYYY-MM-DD)"),
+                ("dir:in dir:out".to_string(), "Money in or out"),
+                bound(Action::Submit, "Keep the search"),
+                bound(Action::Back, "Clear the search"),
+            ];
+        }
+        vec![
+            bound(Action::NextItem, "Next transaction"),
+            bound(Action::PrevItem, "Previous transaction"),
+            bound(Action::FirstItem, "First transaction"),
+            bound(Action::LastItem, "Last transaction"),
+            bound(Action::PageDown, "Page down"),
+            bound(Action::PageUp, "Page up"),
+            bound(Action::Search, "Search"),
+            bound(Action::Export, "Export"),
+            bound(
+                Action::Submit,
+                "Show the transaction, or load more on the last row",
+            ),
+            bound(Action::Back, "Back to the sidebar"),
+        ]
+    }
+
+    fn navigate(&mut self) -> Option<Navigate> {
+        self.navigate.take()
+    }
+
+    fn on_action(&mut self, action: Action) -> bool {
+        self.detail = None;
+        match action {
+            Action::Search => {
+                self.searching = true;
+                activate(&mut self.search);
+            }
+            Action::Export => {
+                self.status = None;
+                self.exporting = true;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    async fn make_progress(&mut self) -> Result<bool, ApiError> {
+        if self.syncing {
+            self.sync_items().await?;
+            self.syncing = false;
+            self.refresh_items();
+            return Ok(true);
+        }
+
         if self.loading {
-            self.load_more_items().await;
+            self.load_more_items().await?;
             self.loading = false;
-            return true;
+            self.refresh_items();
+            return Ok(true);
         }
-        false
+        Ok(false)
+    }
+
+    // keep showing what's cached
+    fn cancel(&mut self) {
+        self.syncing = false;
+        self.loading = false;
+        self.refresh_items();
+    }
+
+    fn notice(&mut self) -> Option<String> {
+        self.notice.take()
+    }
+
+    fn failure(&mut self) -> Option<String> {
+        self.failure.take()
     }
 
-    fn render(&mut self, f: &mut Frame<CrosstermBackend<StdoutLock>>, area: Rect) {
+    fn render(&mut self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
+        let page_area = area;
+        let area = if self.searching || !self.query.is_empty() {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Min(5)].as_ref())
+                .split(area);
+            f.render_widget(self.search.widget(), chunks[0]);
+            chunks[1]
+        } else {
+            area
+        };
+
         let selected_style = Style::default().add_modifier(Modifier::REVERSED);
-        let normal_style = Style::default().bg(Color::Blue);
+        let normal_style = Style::default().bg(theme::get().header_bg);
         let header_cells = ["Amount", "To", "From", "Date", "Note"]
             .iter()
-            .map(|h| Cell::from(*h).style(Style::default().fg(Color::Red)));
+            .map(|h| Cell::from(*h).style(Style::default().fg(theme::get().header_fg)));
         let header = Row::new(header_cells)
             .style(normal_style)
             .height(1)
@@ -153,32 +675,31 @@ impl<'a> Page for StoriesPage<'a> {
         let rows = self
             .items
             .iter()
-            .map(|item| {
-                let height = item
-                    .iter()
-                    .map(|content| content.chars().filter(|c| *c == '\n').count())
-                    .max()
-                    .unwrap_or(0)
-                    + 1;
+            .zip(self.row_heights())
+            .map(|(item, height)| {
                 let cells = item.iter().enumerate().map(|(i, c)| {
                     let mut cell = Cell::from(c.as_str());
                     // green if +, red if -
                     if i == 0 {
                         if c.starts_with("+") {
-                            cell = cell.style(Style::default().fg(Color::Green));
+                            cell = cell.style(Style::default().fg(theme::get().positive));
                         } else if c.starts_with("-") {
-                            cell = cell.style(Style::default().fg(Color::Red));
+                            cell = cell.style(Style::default().fg(theme::get().negative));
                         }
                     }
                     cell
                 });
-                Row::new(cells).height(height as u16).bottom_margin(1)
+                Row::new(cells).height(height).bottom_margin(1)
             })
             .collect::<Vec<_>>();
 
         let t = Table::new(rows)
             .header(header)
-            .block(Block::default().borders(Borders::ALL))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(self.status.clone().unwrap_or_default()),
+            )
             .highlight_style(selected_style)
             .highlight_symbol(">> ")
             .widths(&[
@@ -188,6 +709,21 @@ impl<'a> Page for StoriesPage<'a> {
                 Constraint::Percentage(15),
                 Constraint::Percentage(45),
             ]);
+        // inside the borders, under the header and its margin
+        let heights = self.row_heights();
+        self.offset = first_visible(
+            &heights,
+            1,
+            self.state.selected(),
+            self.offset,
+            area.height.saturating_sub(4),
+        );
+        self.table_area = area;
         f.render_stateful_widget(t, area, &mut self.state);
+
+        if self.exporting {
+            self.render_export_popup(f, page_area);
+        }
+        self.render_detail(f, page_area);
     }
 }
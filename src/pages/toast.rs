@@ -0,0 +1,80 @@
+use std::{
+    collections::VecDeque,
+    io::Stdout,
+    time::{Duration, Instant},
+};
+
+use tui::{
+    backend::CrosstermBackend,
+    layout::Rect,
+    style::Style,
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::theme;
+
+// how long each notification stays up
+const TOAST_TIME: Duration = Duration::from_secs(4);
+
+// transient notifications in the bottom right corner, one at a time
+#[derive(Default)]
+pub struct Toasts {
+    // each message, and whether it's an error
+    queue: VecDeque<(String, bool)>,
+    // when the front one was first drawn
+    shown_at: Option<Instant>,
+}
+
+impl Toasts {
+    pub fn push(&mut self, msg: String) {
+        self.queue.push_back((msg, false));
+    }
+
+    pub fn push_error(&mut self, msg: String) {
+        self.queue.push_back((msg, true));
+    }
+
+    // how long the current toast has left, if one is up
+    pub fn remaining(&self) -> Option<Duration> {
+        self.shown_at
+            .map(|t| TOAST_TIME.saturating_sub(t.elapsed()))
+    }
+
+    pub fn expire(&mut self) {
+        self.queue.pop_front();
+        self.shown_at = None;
+    }
+
+    pub fn render(&mut self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
+        let Some((msg, error)) = self.queue.front() else {
+            return;
+        };
+        self.shown_at.get_or_insert_with(Instant::now);
+
+        let width = (msg.chars().count() as u16 + 4).min(area.width);
+        let height = 3.min(area.height);
+        let toast = Rect {
+            x: area.right() - width,
+            y: area.bottom() - height,
+            width,
+            height,
+        };
+        let colour = if *error {
+            theme::get().error
+        } else {
+            theme::get().positive
+        };
+        f.render_widget(Clear, toast);
+        f.render_widget(
+            Paragraph::new(msg.as_str())
+                .style(Style::default().fg(colour))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .style(Style::default().bg(theme::get().surface)),
+                ),
+            toast,
+        );
+    }
+}
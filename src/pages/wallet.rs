@@ -0,0 +1,351 @@
+use std::io::Stdout;
+
+use async_trait::async_trait;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    Frame,
+};
+use tui_textarea::Input;
+
+use crate::{
+    api::{Api, ApiError},
+    keymap::{self, Action, PAGE_STEP},
+    theme,
+    types::{format_dollars, FundingInstrument, InstrumentFee},
+};
+
+use super::{bound, home::StatefulList, Page};
+
+// everything in the venmo wallet, and which of it payments start on
+pub struct WalletPage<'a> {
+    api: &'a mut Api,
+    loading: bool,
+    instruments: StatefulList<FundingInstrument>,
+    default: Option<String>,
+    notice: Option<String>,
+    failure: Option<String>,
+}
+
+// a warning for cards that have expired or are about to
+fn expiry_warning(instrument: &FundingInstrument) -> Option<String> {
+    let meta = instrument.metadata.as_ref()?;
+    let status = meta.expiration_status.as_deref()?.to_lowercase();
+    if status == "expired" {
+        return Some("expired".to_string());
+    }
+    if status.contains("expir") {
+        return Some(match &meta.expiration_date {
+            Some(date) => format!("expires {date}"),
+            None => "expires soon".to_string(),
+        });
+    }
+    None
+}
+
+// e.g. "3% + $0.25", or "none"
+fn describe_fees(fees: &[InstrumentFee]) -> String {
+    let fees = fees
+        .iter()
+        .filter_map(|f| {
+            let mut parts = vec![];
+            if let Some(pct) = f.variable_percentage.filter(|p| *p > 0.0) {
+                parts.push(format!("{pct}%"));
+            }
+            if let Some(fixed) = f.fixed_amount.filter(|a| *a > 0.0) {
+                parts.push(format_dollars(fixed.round() as i64));
+            }
+            (!parts.is_empty()).then(|| parts.join(" + "))
+        })
+        .collect::<Vec<_>>();
+    if fees.is_empty() {
+        "none".to_string()
+    } else {
+        fees.join(", ")
+    }
+}
+
+impl<'a> WalletPage<'a> {
+    pub fn new(api: &'a mut Api) -> Self {
+        let instruments = api.cache.funding_instruments().unwrap_or_default();
+        Self {
+            // only go to venmo if there's nothing cached yet, or on refresh
+            loading: instruments.is_empty() && !api.offline,
+            instruments: StatefulList::with_items(instruments),
+            default: api.default_funding_source(),
+            notice: None,
+            failure: None,
+            api,
+        }
+    }
+
+    fn selected(&self) -> Option<&FundingInstrument> {
+        self.instruments
+            .state
+            .selected()
+            .and_then(|i| self.instruments.items.get(i))
+    }
+
+    // make the selected instrument the default, or go back to the config's if it already is
+    async fn toggle_default(&mut self) {
+        let Some(instrument) = self.selected() else {
+            return;
+        };
+        let (id, label) = (instrument.id.clone(), instrument.label());
+        if !instrument.peer_allowed() {
+            self.failure = Some(format!("{label} can't pay people"));
+            return;
+        }
+
+        let unset = self.default.as_deref() == Some(id.as_str());
+        let saved = self
+            .api
+            .set_default_funding_source((!unset).then_some(id.as_str()))
+            .await;
+        self.default = self.api.default_funding_source();
+        if let Err(e) = saved {
+            self.failure = Some(e.to_string());
+            return;
+        }
+        self.notice = Some(if !unset {
+            format!("paying with {label} by default")
+        } else if self.default.as_deref() == Some(id.as_str()) {
+            // the config's default_funding_source takes over again, and it may be this one
+            format!("{label} is still the default, from the config")
+        } else {
+            format!("{label} is no longer the default")
+        });
+    }
+
+    fn details(&self, instrument: &FundingInstrument) -> Vec<Spans<'static>> {
+        let meta = instrument.metadata.clone().unwrap_or_default();
+        let roles = instrument.roles.clone().unwrap_or_default();
+        let line = |label: &str, value: String| {
+            Spans::from(vec![
+                Span::styled(
+                    format!("{label:<16}"),
+                    Style::default().fg(theme::get().muted),
+                ),
+                Span::styled(value, Style::default().fg(theme::get().text)),
+            ])
+        };
+
+        let mut lines = vec![
+            line("Name", instrument.name.clone()),
+            line("Type", instrument.instrument_type.to_lowercase()),
+        ];
+        if let Some(bank) = meta.bank_name {
+            lines.push(line("Bank", bank));
+        }
+        if let Some(issuer) = meta.issuer_name {
+            lines.push(line("Issuer", issuer));
+        }
+        if let Some(network) = meta.network_name {
+            lines.push(line("Network", network));
+        }
+        if let Some(last_four) = meta.last_four_digits {
+            lines.push(line("Number", format!("••{last_four}")));
+        }
+        if let Some(verified) = meta.is_verified {
+            lines.push(line(
+                "Verified",
+                if verified { "yes" } else { "no" }.to_string(),
+            ));
+        }
+        if meta.is_venmo_card == Some(true) {
+            lines.push(line("Venmo card", "yes".to_string()));
+        }
+        if let Some(date) = meta.expiration_date {
+            lines.push(line("Expires", date));
+        }
+        if let Some(balance) = meta.available_balance.and_then(|b| b.display_string) {
+            lines.push(line("Available", balance));
+        }
+        lines.push(line("Fees", describe_fees(&instrument.fees)));
+        lines.push(line(
+            "Paying people",
+            roles.peer_payments.unwrap_or_else(|| "unknown".to_string()),
+        ));
+        lines.push(line(
+            "Paying merchants",
+            roles
+                .merchant_payments
+                .unwrap_or_else(|| "unknown".to_string()),
+        ));
+        lines.push(line("Id", instrument.id.clone()));
+        if let Some(warning) = expiry_warning(instrument) {
+            lines.push(Spans::from(""));
+            lines.push(Spans::from(Span::styled(
+                format!("This card {warning}."),
+                Style::default().fg(theme::get().error),
+            )));
+        }
+        lines
+    }
+}
+
+#[async_trait]
+impl<'a> Page for WalletPage<'a> {
+    async fn on_input_event(&mut self, event: Input) -> bool {
+        let instruments = &mut self.instruments;
+        let any = !instruments.items.is_empty();
+        match keymap::get().action(&event, false) {
+            Some(Action::NextItem) if any => instruments.next(),
+            Some(Action::PrevItem) if any => instruments.previous(),
+            Some(Action::FirstItem) if any => instruments.first(),
+            Some(Action::LastItem) if any => instruments.last(),
+            Some(Action::PageDown) if any => instruments.forward(PAGE_STEP),
+            Some(Action::PageUp) if any => instruments.back(PAGE_STEP),
+            Some(Action::Submit) => self.toggle_default().await,
+            Some(Action::Left) | Some(Action::Back) => return true,
+            _ => {}
+        }
+        false
+    }
+
+    async fn on_mouse_event(&mut self, event: MouseEvent) {
+        if self.instruments.items.is_empty() {
+            return;
+        }
+        match event.kind {
+            MouseEventKind::ScrollDown => self.instruments.next(),
+            MouseEventKind::ScrollUp => self.instruments.previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(i) = self.instruments.item_at(&event) {
+                    self.instruments.state.select(Some(i));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    async fn make_progress(&mut self) -> Result<bool, ApiError> {
+        if self.loading {
+            let selected = self.selected().map(|i| i.id.clone());
+            self.instruments = StatefulList::with_items(self.api.get_funding_instruments().await?);
+            // stay on the same instrument across a refresh
+            if let Some(i) =
+                selected.and_then(|id| self.instruments.items.iter().position(|v| v.id == id))
+            {
+                self.instruments.state.select(Some(i));
+            }
+            self.loading = false;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // keep showing what's cached
+    fn cancel(&mut self) {
+        self.loading = false;
+    }
+
+    fn notice(&mut self) -> Option<String> {
+        self.notice.take()
+    }
+
+    fn failure(&mut self) -> Option<String> {
+        self.failure.take()
+    }
+
+    fn on_action(&mut self, action: Action) -> bool {
+        if action == Action::Refresh && !self.api.offline {
+            self.loading = true;
+            return true;
+        }
+        false
+    }
+
+    fn help(&self) -> Vec<(String, &'static str)> {
+        vec![
+            bound(Action::NextItem, "Next instrument"),
+            bound(Action::PrevItem, "Previous instrument"),
+            bound(Action::Submit, "Make it the default for payments"),
+            bound(Action::Back, "Back to the sidebar"),
+        ]
+    }
+
+    fn render(&mut self, f: &mut Frame<CrosstermBackend<Stdout>>, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(4), Constraint::Min(5)].as_ref())
+            .split(area);
+
+        let default = self
+            .instruments
+            .items
+            .iter()
+            .find(|i| Some(&i.id) == self.default.as_ref())
+            .map(|i| i.label())
+            .unwrap_or_else(|| "none".to_string());
+        let header = Paragraph::new(vec![
+            Spans::from(format!(
+                "Paying with {default} by default{}",
+                if self.loading { "   (syncing...)" } else { "" }
+            )),
+            Spans::from(format!(
+                "{}: make default   {}: refresh",
+                keymap::get().describe(Action::Submit),
+                keymap::get().describe(Action::Refresh)
+            )),
+        ])
+        .block(Block::default().borders(Borders::ALL).title(Span::styled(
+            if self.api.offline {
+                "Wallet (offline)"
+            } else {
+                "Wallet"
+            },
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        f.render_widget(header, chunks[0]);
+
+        let body = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(chunks[1]);
+
+        let items = self
+            .instruments
+            .items
+            .iter()
+            .map(|i| {
+                let color = if i.peer_allowed() {
+                    theme::get().text
+                } else {
+                    theme::get().muted
+                };
+                let mut spans = vec![Span::styled(i.label(), Style::default().fg(color))];
+                if Some(&i.id) == self.default.as_ref() {
+                    spans.push(Span::styled(
+                        "  default",
+                        Style::default().fg(theme::get().accent),
+                    ));
+                }
+                if let Some(warning) = expiry_warning(i) {
+                    spans.push(Span::styled(
+                        format!("  {warning}"),
+                        Style::default().fg(theme::get().error),
+                    ));
+                }
+                ListItem::new(Spans::from(spans))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Instruments"))
+            .highlight_style(theme::get().highlight());
+        self.instruments.track(body[0]);
+        f.render_stateful_widget(list, body[0], &mut self.instruments.state);
+
+        let details = self.selected().map(|i| self.details(i)).unwrap_or_default();
+        f.render_widget(
+            Paragraph::new(details)
+                .wrap(Wrap { trim: false })
+                .block(Block::default().borders(Borders::ALL).title("Details")),
+            body[1],
+        );
+    }
+}
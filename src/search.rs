@@ -0,0 +1,337 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+
+use crate::types::{SenderReciever, Story};
+
+#[derive(Debug)]
+pub enum QueryError {
+    InvalidAmount(String),
+    InvalidDate(String),
+    InvalidDirection(String),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::InvalidAmount(e) => {
+                write!(f, "invalid amount '{e}' (try 20, >20, <=5 or 10..50)")
+            }
+            QueryError::InvalidDate(e) => write!(f, "invalid date '{e}' (use YYYY-MM-DD)"),
+            QueryError::InvalidDirection(e) => write!(f, "invalid direction '{e}' (use in or out)"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+// a parsed search like `from:@alice amount:>20 after:2026-01-01 pizza`.
+//
+// supported filters:
+//   from:<name>, to:<name>, with:<name>  counterparty username or display name,
+//                                        or exactly that username for @handle
+//   amount:20, amount:>20, amount:<=5, amount:10..50  dollars, ignoring sign
+//   dir:in, dir:out                       money received or sent
+//   after:YYYY-MM-DD, before:YYYY-MM-DD   on or after / strictly before
+// anything else (or "quoted text") must appear in the note or a counterparty name.
+#[derive(Default, Debug, Clone)]
+pub struct Query {
+    pub terms: Vec<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub with: Option<String>,
+    pub min_cents: Option<i64>,
+    pub max_cents: Option<i64>,
+    pub direction: Option<Direction>,
+    pub after: Option<NaiveDate>,
+    pub before: Option<NaiveDate>,
+}
+
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+fn parse_cents(value: &str) -> Result<i64, QueryError> {
+    value
+        .trim_start_matches('$')
+        .parse::<f64>()
+        .map(|v| (v * 100.0).round() as i64)
+        .map_err(|_| QueryError::InvalidAmount(value.to_string()))
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, QueryError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| QueryError::InvalidDate(value.to_string()))
+}
+
+// `needle` is lowercase. "@bob" is bob's handle and nobody else's, "bob" is anyone with it in
+// their username or display name
+fn name_matches(person: Option<&SenderReciever>, needle: &str) -> bool {
+    person.is_some_and(|p| match needle.strip_prefix('@') {
+        Some(handle) => p.username.to_lowercase() == handle,
+        None => {
+            p.username.to_lowercase().contains(needle)
+                || p.display_name.to_lowercase().contains(needle)
+        }
+    })
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, QueryError> {
+        let mut query = Query::default();
+
+        for token in tokenize(input) {
+            let (key, value) = match token.split_once(':') {
+                Some((k, v)) if !v.is_empty() => (k.to_lowercase(), v),
+                _ => {
+                    query.terms.push(token.to_lowercase());
+                    continue;
+                }
+            };
+
+            let name = || value.to_lowercase();
+
+            match key.as_str() {
+                "from" => query.from = Some(name()),
+                "to" => query.to = Some(name()),
+                "with" => query.with = Some(name()),
+                "amount" => {
+                    if let Some((lo, hi)) = value.split_once("..") {
+                        query.min_cents = Some(parse_cents(lo)?);
+                        query.max_cents = Some(parse_cents(hi)?);
+                    } else if let Some(v) = value.strip_prefix(">=") {
+                        query.min_cents = Some(parse_cents(v)?);
+                    } else if let Some(v) = value.strip_prefix("<=") {
+                        query.max_cents = Some(parse_cents(v)?);
+                    } else if let Some(v) = value.strip_prefix('>') {
+                        query.min_cents = Some(parse_cents(v)? + 1);
+                    } else if let Some(v) = value.strip_prefix('<') {
+                        query.max_cents = Some(parse_cents(v)? - 1);
+                    } else {
+                        let cents = parse_cents(value)?;
+                        query.min_cents = Some(cents);
+                        query.max_cents = Some(cents);
+                    }
+                }
+                "dir" => {
+                    query.direction = Some(match value.to_lowercase().as_str() {
+                        "in" => Direction::In,
+                        "out" => Direction::Out,
+                        _ => return Err(QueryError::InvalidDirection(value.to_string())),
+                    })
+                }
+                "after" => query.after = Some(parse_date(value)?),
+                "before" => query.before = Some(parse_date(value)?),
+                // not a filter we know, so just search for it
+                _ => query.terms.push(token.to_lowercase()),
+            }
+        }
+
+        Ok(query)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+            && self.from.is_none()
+            && self.to.is_none()
+            && self.with.is_none()
+            && self.min_cents.is_none()
+            && self.max_cents.is_none()
+            && self.direction.is_none()
+            && self.after.is_none()
+            && self.before.is_none()
+    }
+
+    pub fn matches(&self, story: &Story) -> bool {
+        let sender = story.title.sender.as_ref();
+        let receiver = story.title.receiver.as_ref();
+
+        if let Some(from) = &self.from {
+            if !name_matches(sender, from) {
+                return false;
+            }
+        }
+
+        if let Some(to) = &self.to {
+            if !name_matches(receiver, to) {
+                return false;
+            }
+        }
+
+        if let Some(with) = &self.with {
+            if !name_matches(sender, with) && !name_matches(receiver, with) {
+                return false;
+            }
+        }
+
+        if self.min_cents.is_some() || self.max_cents.is_some() || self.direction.is_some() {
+            let Some(cents) = story.amount_cents() else {
+                return false;
+            };
+
+            if self.min_cents.is_some_and(|min| cents.abs() < min)
+                || self.max_cents.is_some_and(|max| cents.abs() > max)
+            {
+                return false;
+            }
+
+            match self.direction {
+                Some(Direction::In) if cents < 0 => return false,
+                Some(Direction::Out) if cents > 0 => return false,
+                _ => {}
+            }
+        }
+
+        if self.after.is_some() || self.before.is_some() {
+            let Some(day) = story.day() else {
+                return false;
+            };
+
+            if self.after.is_some_and(|after| day < after)
+                || self.before.is_some_and(|before| day >= before)
+            {
+                return false;
+            }
+        }
+
+        let note = story
+            .note
+            .content
+            .as_deref()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        self.terms.iter().all(|term| {
+            note.contains(term.as_str())
+                || name_matches(sender, term)
+                || name_matches(receiver, term)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn quoted_terms_stay_together() {
+        let query = Query::parse(r#"pizza "Friday Night" to:@Bob"#).unwrap();
+        assert_eq!(query.terms, vec!["pizza", "friday night"]);
+        assert_eq!(query.to.as_deref(), Some("@bob"));
+    }
+
+    #[test]
+    fn unknown_or_empty_filters_are_terms() {
+        let query = Query::parse("note: foo:bar").unwrap();
+        assert_eq!(query.terms, vec!["note:", "foo:bar"]);
+    }
+
+    #[test]
+    fn date_ranges() {
+        let query = Query::parse("after:2026-01-01 before:2026-02-01").unwrap();
+        assert_eq!(query.after, Some(date("2026-01-01")));
+        assert_eq!(query.before, Some(date("2026-02-01")));
+        assert!(matches!(
+            Query::parse("after:01/01/2026"),
+            Err(QueryError::InvalidDate(_))
+        ));
+    }
+
+    #[test]
+    fn amount_comparisons() {
+        let exact = Query::parse("amount:$12.50").unwrap();
+        assert_eq!((exact.min_cents, exact.max_cents), (Some(1250), Some(1250)));
+
+        let range = Query::parse("amount:10..50").unwrap();
+        assert_eq!((range.min_cents, range.max_cents), (Some(1000), Some(5000)));
+
+        let over = Query::parse("amount:>20").unwrap();
+        assert_eq!((over.min_cents, over.max_cents), (Some(2001), None));
+
+        let at_least = Query::parse("amount:>=20").unwrap();
+        assert_eq!(at_least.min_cents, Some(2000));
+
+        let under = Query::parse("amount:<5").unwrap();
+        assert_eq!((under.min_cents, under.max_cents), (None, Some(499)));
+
+        let at_most = Query::parse("amount:<=5").unwrap();
+        assert_eq!(at_most.max_cents, Some(500));
+
+        assert!(matches!(
+            Query::parse("amount:lots"),
+            Err(QueryError::InvalidAmount(_))
+        ));
+    }
+
+    #[test]
+    fn direction() {
+        assert_eq!(
+            Query::parse("dir:IN").unwrap().direction,
+            Some(Direction::In)
+        );
+        assert!(matches!(
+            Query::parse("dir:sideways"),
+            Err(QueryError::InvalidDirection(_))
+        ));
+    }
+
+    #[test]
+    fn matches_stories() {
+        let paid = Story::fixture("1", "2026-01-15T12:00:00", "- $25.00", "bob", "Pizza night");
+        let received = Story::fixture("2", "2026-02-01T12:00:00", "+ $5.00", "alice", "coffee");
+
+        let query = Query::parse("to:bob amount:>20 dir:out pizza").unwrap();
+        assert!(query.matches(&paid));
+        assert!(!query.matches(&received));
+
+        // before is exclusive, after is inclusive
+        let january = Query::parse("after:2026-01-15 before:2026-02-01").unwrap();
+        assert!(january.matches(&paid));
+        assert!(!january.matches(&received));
+
+        assert!(Query::parse("with:ALICE").unwrap().matches(&received));
+        assert!(Query::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn handles_match_exactly() {
+        let bob = Story::fixture("1", "2026-01-15T12:00:00", "- $25.00", "bob", "");
+        let bobby = Story::fixture("2", "2026-01-16T12:00:00", "- $5.00", "bobby", "");
+
+        let handle = Query::parse("with:@Bob").unwrap();
+        assert!(handle.matches(&bob));
+        assert!(!handle.matches(&bobby));
+
+        let name = Query::parse("with:bob").unwrap();
+        assert!(name.matches(&bob));
+        assert!(name.matches(&bobby));
+    }
+}
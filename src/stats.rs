@@ -0,0 +1,403 @@
+use std::{collections::HashMap, fmt};
+
+use chrono::{Datelike, Months, NaiveDate};
+
+use crate::types::{Story, StorySubType};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PeriodKind {
+    Month,
+    Quarter,
+    Year,
+    Custom,
+}
+
+// a window of days, `start` inclusive and `end` exclusive
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Period {
+    pub kind: PeriodKind,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+fn first_of_month(day: NaiveDate) -> NaiveDate {
+    day.with_day(1).unwrap()
+}
+
+impl Period {
+    // the month/quarter/year containing `day`
+    pub fn containing(kind: PeriodKind, day: NaiveDate) -> Self {
+        let (start, months) = match kind {
+            PeriodKind::Month | PeriodKind::Custom => (first_of_month(day), 1),
+            PeriodKind::Quarter => (
+                NaiveDate::from_ymd_opt(day.year(), (day.month0() / 3) * 3 + 1, 1).unwrap(),
+                3,
+            ),
+            PeriodKind::Year => (NaiveDate::from_ymd_opt(day.year(), 1, 1).unwrap(), 12),
+        };
+
+        Period {
+            kind,
+            start,
+            end: start + Months::new(months),
+        }
+    }
+
+    // `end` is the last day to include
+    pub fn custom(start: NaiveDate, end: NaiveDate) -> Self {
+        Period {
+            kind: PeriodKind::Custom,
+            start,
+            end: end.succ_opt().unwrap_or(end),
+        }
+    }
+
+    fn shift(&self, forward: bool) -> Self {
+        match self.kind {
+            PeriodKind::Custom => {
+                let len = self.end - self.start;
+                let (start, end) = if forward {
+                    (self.end, self.end + len)
+                } else {
+                    (self.start - len, self.start)
+                };
+                Period {
+                    start,
+                    end,
+                    ..*self
+                }
+            }
+            kind => {
+                let day = if forward {
+                    self.end
+                } else {
+                    self.start.pred_opt().unwrap()
+                };
+                Period::containing(kind, day)
+            }
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        self.shift(true)
+    }
+
+    pub fn previous(&self) -> Self {
+        self.shift(false)
+    }
+
+    // first day of every month touched by the period
+    pub fn months(&self) -> Vec<NaiveDate> {
+        let mut months = vec![];
+        let mut month = first_of_month(self.start);
+        while month < self.end {
+            months.push(month);
+            month = month + Months::new(1);
+        }
+        months
+    }
+
+    // search syntax selecting the same stories, e.g. for drilling into the story list
+    pub fn query(&self) -> String {
+        format!("after:{} before:{}", self.start, self.end)
+    }
+
+    pub fn contains(&self, day: NaiveDate) -> bool {
+        self.start <= day && day < self.end
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            PeriodKind::Month => write!(f, "{}", self.start.format("%B %Y")),
+            PeriodKind::Quarter => {
+                write!(f, "Q{} {}", self.start.month0() / 3 + 1, self.start.year())
+            }
+            PeriodKind::Year => write!(f, "{}", self.start.year()),
+            PeriodKind::Custom => write!(
+                f,
+                "{} to {}",
+                self.start,
+                self.end.pred_opt().unwrap_or(self.end)
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MonthTotals {
+    pub month: NaiveDate,
+    pub in_cents: i64,
+    pub out_cents: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct CounterpartyTotals {
+    pub username: String,
+    pub display_name: String,
+    pub in_cents: i64,
+    pub out_cents: i64,
+    pub count: usize,
+}
+
+impl CounterpartyTotals {
+    pub fn volume(&self) -> i64 {
+        self.in_cents + self.out_cents
+    }
+
+    // positive when they've paid us more than we've paid them
+    pub fn net(&self) -> i64 {
+        self.in_cents - self.out_cents
+    }
+}
+
+// whether cached stories (newest first) go back far enough for totals from `start` on to be
+// complete. no start means all time, which needs the whole history
+pub fn covers(stories: &[Story], reached_end: bool, start: Option<NaiveDate>) -> bool {
+    reached_end
+        || start.is_some_and(|start| {
+            stories
+                .last()
+                .and_then(|s| s.day())
+                .is_some_and(|d| d < start)
+        })
+}
+
+// p2p stories with a parseable day and amount, optionally limited to a period, oldest first
+pub fn payments<'a>(
+    stories: &'a [Story],
+    period: Option<&Period>,
+) -> Vec<(&'a Story, NaiveDate, i64)> {
+    let mut payments = stories
+        .iter()
+        .filter(|s| matches!(s.title.payload.sub_type, StorySubType::P2p))
+        .filter_map(|s| Some((s, s.day()?, s.amount_cents()?)))
+        .filter(|(_, day, _)| period.is_none_or(|p| p.contains(*day)))
+        .collect::<Vec<_>>();
+    payments.sort_by_key(|(s, _, _)| s.date.clone());
+    payments
+}
+
+// money in and out per person, busiest first. no period means all time
+pub fn counterparties(stories: &[Story], period: Option<&Period>) -> Vec<CounterpartyTotals> {
+    let mut totals: HashMap<String, CounterpartyTotals> = HashMap::new();
+
+    for (story, _, cents) in payments(stories, period) {
+        let Some(person) = story.counterparty() else {
+            continue;
+        };
+
+        let t = totals
+            .entry(person.username.clone())
+            .or_insert_with(|| CounterpartyTotals {
+                username: person.username.clone(),
+                display_name: person.display_name.clone(),
+                in_cents: 0,
+                out_cents: 0,
+                count: 0,
+            });
+        if cents < 0 {
+            t.out_cents -= cents;
+        } else {
+            t.in_cents += cents;
+        }
+        t.count += 1;
+    }
+
+    let mut totals = totals.into_values().collect::<Vec<_>>();
+    totals.sort_by(|a, b| {
+        b.volume()
+            .cmp(&a.volume())
+            .then(a.username.cmp(&b.username))
+    });
+    totals
+}
+
+// payments within a period, aggregated. transfers to and from the bank aren't
+// spending, so only p2p stories count
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    pub months: Vec<MonthTotals>,
+    // busiest first
+    pub counterparties: Vec<CounterpartyTotals>,
+    pub count: usize,
+    pub in_cents: i64,
+    pub out_cents: i64,
+    // net position after each story, oldest first
+    pub running_net: Vec<i64>,
+}
+
+impl Summary {
+    pub fn new(stories: &[Story], period: &Period) -> Self {
+        let mut summary = Summary {
+            months: period
+                .months()
+                .into_iter()
+                .map(|month| MonthTotals {
+                    month,
+                    in_cents: 0,
+                    out_cents: 0,
+                })
+                .collect(),
+            counterparties: counterparties(stories, Some(period)),
+            ..Summary::default()
+        };
+        let mut net = 0;
+
+        for (_, day, cents) in payments(stories, Some(period)) {
+            let (in_cents, out_cents) = if cents < 0 { (0, -cents) } else { (cents, 0) };
+
+            summary.count += 1;
+            summary.in_cents += in_cents;
+            summary.out_cents += out_cents;
+
+            if let Some(m) = summary
+                .months
+                .iter_mut()
+                .find(|m| m.month == first_of_month(day))
+            {
+                m.in_cents += in_cents;
+                m.out_cents += out_cents;
+            }
+
+            net += cents;
+            summary.running_net.push(net);
+        }
+
+        summary
+    }
+
+    pub fn net_cents(&self) -> i64 {
+        self.in_cents - self.out_cents
+    }
+
+    pub fn average_cents(&self) -> i64 {
+        if self.count == 0 {
+            return 0;
+        }
+        (self.in_cents + self.out_cents) / self.count as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn stories() -> Vec<Story> {
+        // newest first, like the cache hands them out
+        vec![
+            Story::fixture("5", "2026-04-02T12:00:00", "+ $5.00", "alice", ""),
+            Story::fixture("4", "2026-03-20T12:00:00", "- $7.50", "bob", ""),
+            Story::fixture("3", "2026-03-01T12:00:00", "+ $20.00", "bob", ""),
+            Story::fixture("2", "2026-02-10T12:00:00", "- $12.00", "alice", ""),
+            Story::fixture("1", "2025-12-31T12:00:00", "- $100.00", "carol", ""),
+        ]
+    }
+
+    #[test]
+    fn periods_contain_their_day() {
+        let day = date("2026-05-17");
+        let month = Period::containing(PeriodKind::Month, day);
+        assert_eq!(
+            (month.start, month.end),
+            (date("2026-05-01"), date("2026-06-01"))
+        );
+        let quarter = Period::containing(PeriodKind::Quarter, day);
+        assert_eq!(
+            (quarter.start, quarter.end),
+            (date("2026-04-01"), date("2026-07-01"))
+        );
+        let year = Period::containing(PeriodKind::Year, day);
+        assert_eq!(
+            (year.start, year.end),
+            (date("2026-01-01"), date("2027-01-01"))
+        );
+
+        // end is exclusive
+        assert!(month.contains(date("2026-05-31")));
+        assert!(!month.contains(date("2026-06-01")));
+    }
+
+    #[test]
+    fn periods_step_across_years() {
+        let q1 = Period::containing(PeriodKind::Quarter, date("2026-02-14"));
+        assert_eq!(q1.previous().start, date("2025-10-01"));
+        assert_eq!(q1.previous().next(), q1);
+        assert_eq!(q1.to_string(), "Q1 2026");
+
+        let december = Period::containing(PeriodKind::Month, date("2025-12-05"));
+        assert_eq!(december.next().start, date("2026-01-01"));
+    }
+
+    #[test]
+    fn custom_periods_include_the_end_day_and_step_by_their_length() {
+        let week = Period::custom(date("2026-03-01"), date("2026-03-07"));
+        assert!(week.contains(date("2026-03-07")));
+        assert_eq!(week.to_string(), "2026-03-01 to 2026-03-07");
+        let next = week.next();
+        assert_eq!(
+            (next.start, next.end),
+            (date("2026-03-08"), date("2026-03-15"))
+        );
+        assert_eq!(week.months(), vec![date("2026-03-01")]);
+    }
+
+    #[test]
+    fn summary_totals_the_period() {
+        let q1 = Period::containing(PeriodKind::Quarter, date("2026-01-01"));
+        let summary = Summary::new(&stories(), &q1);
+
+        assert_eq!(summary.count, 3);
+        assert_eq!((summary.in_cents, summary.out_cents), (2000, 1950));
+        assert_eq!(summary.net_cents(), 50);
+        assert_eq!(summary.average_cents(), 1316);
+        // oldest first
+        assert_eq!(summary.running_net, vec![-1200, 800, 50]);
+
+        let months = summary
+            .months
+            .iter()
+            .map(|m| (m.month, m.in_cents, m.out_cents))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            months,
+            vec![
+                (date("2026-01-01"), 0, 0),
+                (date("2026-02-01"), 0, 1200),
+                (date("2026-03-01"), 2000, 750),
+            ]
+        );
+    }
+
+    #[test]
+    fn counterparties_net_per_person_busiest_first() {
+        let people = counterparties(&stories(), None);
+        let totals = people
+            .iter()
+            .map(|p| (p.username.as_str(), p.net(), p.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            totals,
+            vec![("carol", -10000, 1), ("bob", 1250, 2), ("alice", -700, 2)]
+        );
+
+        let q1 = Period::containing(PeriodKind::Quarter, date("2026-01-01"));
+        assert_eq!(counterparties(&stories(), Some(&q1)).len(), 2);
+    }
+
+    #[test]
+    fn coverage_needs_a_story_before_the_start() {
+        let stories = stories();
+        assert!(covers(&stories, false, Some(date("2026-01-01"))));
+        assert!(!covers(&stories, false, Some(date("2025-12-31"))));
+        // all time is only complete at the end of history
+        assert!(!covers(&stories, false, None));
+        assert!(covers(&stories, true, None));
+        assert!(!covers(&[], false, Some(date("2026-01-01"))));
+    }
+}
@@ -0,0 +1,270 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use serde::Deserialize;
+use tui::style::{Color, Modifier, Style};
+
+use crate::config;
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+pub const BUILT_IN: [&str; 3] = ["dark", "light", "high-contrast"];
+
+const SLOTS: [&str; 13] = [
+    "accent",
+    "text",
+    "muted",
+    "positive",
+    "negative",
+    "error",
+    "warning_fg",
+    "warning_bg",
+    "surface",
+    "header_fg",
+    "header_bg",
+    "qr_dark",
+    "qr_light",
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    // selections, focused inputs and titles
+    pub accent: Color,
+    // list and popup text
+    pub text: Color,
+    // unfocused inputs and things that don't need attention
+    pub muted: Color,
+    // money in
+    pub positive: Color,
+    // money out
+    pub negative: Color,
+    // invalid input and error messages
+    pub error: Color,
+    // the offline banner
+    pub warning_fg: Color,
+    pub warning_bg: Color,
+    // popup backgrounds
+    pub surface: Color,
+    // table headers
+    pub header_fg: Color,
+    pub header_bg: Color,
+    pub qr_dark: Color,
+    pub qr_light: Color,
+    // NO_COLOR is set, so lean on modifiers instead
+    pub plain: bool,
+}
+
+// a [themes.<name>] config table: a built-in theme to start from plus any
+// colors to replace, e.g. accent = "#ff8800"
+#[derive(Deserialize, Debug, Clone)]
+pub struct ThemeSpec {
+    #[serde(default = "default_base")]
+    pub base: String,
+    #[serde(flatten)]
+    pub colors: HashMap<String, String>,
+}
+
+fn default_base() -> String {
+    "dark".to_string()
+}
+
+// "blue", "light-red", "#ff8800" or a 0-255 palette index
+pub fn parse_color(s: &str) -> Result<Color, String> {
+    let name = s.to_lowercase().replace(['-', '_', ' '], "");
+
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() == 6 {
+            if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+                return Ok(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+            }
+        }
+    }
+    if let Ok(i) = name.parse::<u8>() {
+        return Ok(Color::Indexed(i));
+    }
+
+    Ok(match name.as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => {
+            return Err(format!(
+                "unknown color '{s}' (use a name like light-blue, #rrggbb or 0-255)"
+            ))
+        }
+    })
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            accent: Color::LightBlue,
+            text: Color::Reset,
+            muted: Color::DarkGray,
+            positive: Color::LightGreen,
+            negative: Color::LightRed,
+            error: Color::LightRed,
+            warning_fg: Color::Black,
+            warning_bg: Color::Yellow,
+            surface: Color::Reset,
+            header_fg: Color::Black,
+            header_bg: Color::LightBlue,
+            qr_dark: Color::LightBlue,
+            qr_light: Color::White,
+            plain: false,
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            accent: Color::Blue,
+            text: Color::Black,
+            muted: Color::DarkGray,
+            positive: Color::Green,
+            negative: Color::Red,
+            error: Color::Red,
+            surface: Color::White,
+            header_fg: Color::White,
+            header_bg: Color::Blue,
+            qr_dark: Color::Blue,
+            ..Theme::dark()
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Theme {
+            accent: Color::Yellow,
+            text: Color::White,
+            muted: Color::Gray,
+            surface: Color::Black,
+            header_fg: Color::Black,
+            header_bg: Color::Yellow,
+            qr_dark: Color::Black,
+            ..Theme::dark()
+        }
+    }
+
+    // everything in the terminal's own colors
+    pub fn plain() -> Self {
+        Theme {
+            accent: Color::Reset,
+            text: Color::Reset,
+            muted: Color::Reset,
+            positive: Color::Reset,
+            negative: Color::Reset,
+            error: Color::Reset,
+            warning_fg: Color::Reset,
+            warning_bg: Color::Reset,
+            surface: Color::Reset,
+            header_fg: Color::Reset,
+            header_bg: Color::Reset,
+            qr_dark: Color::Reset,
+            qr_light: Color::Reset,
+            plain: true,
+        }
+    }
+
+    fn built_in(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    fn slot(&mut self, name: &str) -> Option<&mut Color> {
+        Some(match name {
+            "accent" => &mut self.accent,
+            "text" => &mut self.text,
+            "muted" => &mut self.muted,
+            "positive" => &mut self.positive,
+            "negative" => &mut self.negative,
+            "error" => &mut self.error,
+            "warning_fg" => &mut self.warning_fg,
+            "warning_bg" => &mut self.warning_bg,
+            "surface" => &mut self.surface,
+            "header_fg" => &mut self.header_fg,
+            "header_bg" => &mut self.header_bg,
+            "qr_dark" => &mut self.qr_dark,
+            "qr_light" => &mut self.qr_light,
+            _ => return None,
+        })
+    }
+
+    // a built-in theme, or one from the config's [themes] tables
+    pub fn resolve(name: &str, custom: &HashMap<String, ThemeSpec>) -> Result<Self, String> {
+        if let Some(theme) = Theme::built_in(name) {
+            return Ok(theme);
+        }
+
+        let Some(spec) = custom.get(name) else {
+            let mut names = BUILT_IN.map(String::from).to_vec();
+            names.extend(custom.keys().cloned());
+            return Err(format!("unknown theme '{name}' (use {})", names.join(", ")));
+        };
+
+        let mut theme = Theme::built_in(&spec.base).ok_or_else(|| {
+            format!(
+                "theme '{name}' has an unknown base '{}' (use {})",
+                spec.base,
+                BUILT_IN.join(", ")
+            )
+        })?;
+        for (slot, color) in spec.colors.iter() {
+            *theme.slot(slot).ok_or_else(|| {
+                format!(
+                    "theme '{name}' has an unknown color '{slot}' (use {})",
+                    SLOTS.join(", ")
+                )
+            })? = parse_color(color).map_err(|e| format!("theme '{name}': {e}"))?;
+        }
+
+        Ok(theme)
+    }
+
+    // the selected row of a list
+    pub fn highlight(&self) -> Style {
+        let style = Style::default()
+            .fg(self.accent)
+            .add_modifier(Modifier::BOLD);
+        if self.plain {
+            style.add_modifier(Modifier::REVERSED)
+        } else {
+            style
+        }
+    }
+
+    pub fn amount(&self, cents: i64) -> Color {
+        if cents < 0 {
+            self.negative
+        } else {
+            self.positive
+        }
+    }
+}
+
+// the configured theme, or plain colors when NO_COLOR is set (https://no-color.org)
+pub fn get() -> &'static Theme {
+    THEME.get_or_init(|| {
+        if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return Theme::plain();
+        }
+        let config = config::get();
+        // the config was validated at startup
+        Theme::resolve(&config.theme, &config.themes).unwrap_or_else(|_| Theme::dark())
+    })
+}
@@ -1,85 +1,88 @@
-use serde::Deserialize;
+use std::str::FromStr;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct LoginResponse {
-    display_name: String,
-    id: String,
-    username: String,
-    first_name: String,
-    last_name: String,
-    profile_picture_url: String,
-    friend_count: u32,
-    initials: String,
-    friend_status: Option<bool>,
-    is_blocked: bool,
-    is_active: bool,
-    identity_type: IdentityType,
-    email: String,
-    phone: String,
-}
-
-#[derive(Deserialize, Debug, Clone)]
+    pub display_name: String,
+    pub id: String,
+    pub username: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub profile_picture_url: String,
+    pub friend_count: u32,
+    pub initials: String,
+    pub friend_status: Option<bool>,
+    pub is_blocked: bool,
+    pub is_active: bool,
+    pub identity_type: IdentityType,
+    pub email: String,
+    pub phone: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
-enum IdentityType {
+pub enum IdentityType {
     Personal,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct UserBalance {
     pub value: f32,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Balance {
     pub user_balance: UserBalance,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Avatar {
-    url: String,
+    pub url: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Identity {
-    is_denylisted: bool,
-    is_suspended: bool,
+    pub is_denylisted: bool,
+    pub is_suspended: bool,
     #[serde(rename = "type")]
-    account_type: IdentityType,
-    avatar: Avatar,
+    pub account_type: IdentityType,
+    pub avatar: Avatar,
     pub display_name: String,
     pub handle: String,
     pub id: String,
     pub balance: Balance,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum StoryType {
     Payment,
     Transfer,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StoryNote {
     #[serde(default)]
     pub content: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SenderReciever {
-    id: String,
+    pub id: String,
     pub display_name: String,
     pub username: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum StorySubType {
     #[serde(rename = "p2p")]
@@ -89,66 +92,435 @@ pub enum StorySubType {
     CreditRepayment,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+impl StorySubType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorySubType::P2p => "p2p",
+            StorySubType::StandardTransfer => "standardTransfer",
+            StorySubType::CreditReward => "creditReward",
+            StorySubType::CreditRepayment => "creditRepayment",
+        }
+    }
+
+    pub const ALL: [StorySubType; 4] = [
+        StorySubType::P2p,
+        StorySubType::StandardTransfer,
+        StorySubType::CreditReward,
+        StorySubType::CreditRepayment,
+    ];
+}
+
+impl FromStr for StorySubType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StorySubType::ALL
+            .into_iter()
+            .find(|t| t.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!(
+                    "unknown story type '{s}' (use {})",
+                    StorySubType::ALL.map(|t| t.as_str()).join(", ")
+                )
+            })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StoryPayload {
     pub sub_type: StorySubType,
+    // "pay" or "charge" (a request) for p2p stories
+    #[serde(default)]
+    pub action: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StoryTitle {
     pub payload: StoryPayload,
     #[serde(default)]
-    content: Option<String>,
+    pub content: Option<String>,
     #[serde(default)]
     pub receiver: Option<SenderReciever>,
     #[serde(default)]
     pub sender: Option<SenderReciever>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Story {
     pub amount: String,
-    avatar: String,
-    initials: String,
+    pub avatar: String,
+    pub initials: String,
     pub date: String,
-    id: String,
+    pub id: String,
     pub note: StoryNote,
     pub title: StoryTitle,
+    // e.g. "private" or "friends"
+    #[serde(default)]
+    pub audience: Option<String>,
+    // e.g. "settled" or "pending"
+    #[serde(default)]
+    pub status: Option<String>,
+    // only there for our own payments
+    #[serde(default)]
+    pub funding_source: Option<StoryFundingSource>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StoryFundingSource {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default, rename = "type")]
+    pub source_type: Option<String>,
+}
+
+impl Story {
+    // signed amount in cents, e.g. "- $12.50" -> -1250
+    pub fn amount_cents(&self) -> Option<i64> {
+        let negative = self.amount.trim_start().starts_with('-');
+        let digits = self
+            .amount
+            .chars()
+            .filter(|c| c.is_ascii_digit() || *c == '.')
+            .collect::<String>();
+
+        let cents = (digits.parse::<f64>().ok()? * 100.0).round() as i64;
+        Some(if negative { -cents } else { cents })
+    }
+
+    // calendar day the story happened on
+    pub fn day(&self) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(self.date.get(..10)?, "%Y-%m-%d").ok()
+    }
+
+    // exact time of the story, in UTC
+    pub fn timestamp(&self) -> Option<NaiveDateTime> {
+        DateTime::parse_from_rfc3339(&self.date)
+            .map(|d| d.naive_utc())
+            .or_else(|_| NaiveDateTime::parse_from_str(&self.date, "%Y-%m-%dT%H:%M:%S%.f"))
+            .ok()
+            .or_else(|| self.day()?.and_hms_opt(0, 0, 0))
+    }
+
+    // the same moment on this machine's clock, for showing to the user
+    pub fn local_time(&self) -> Option<DateTime<Local>> {
+        self.timestamp()
+            .map(|t| Utc.from_utc_datetime(&t).with_timezone(&Local))
+    }
+
+    // the other side of the story: who we paid, or who paid us
+    pub fn counterparty(&self) -> Option<&SenderReciever> {
+        match self.amount_cents() {
+            Some(cents) if cents < 0 => self.title.receiver.as_ref(),
+            _ => self.title.sender.as_ref(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StoriesResponse {
     pub next_id: String,
     pub stories: Vec<Story>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Person {
+    pub id: String,
+    pub display_name: String,
+    pub handle: String,
+    #[serde(default)]
+    pub first_name: Option<String>,
+    #[serde(default)]
+    pub last_name: Option<String>,
+    #[serde(default)]
+    pub is_friend: Option<bool>,
+    #[serde(default)]
+    pub avatar: Option<Avatar>,
+}
+
+// someone from a story, as much of them as the story has
+impl From<&SenderReciever> for Person {
+    fn from(other: &SenderReciever) -> Self {
+        Person {
+            id: other.id.clone(),
+            display_name: other.display_name.clone(),
+            handle: other.username.clone(),
+            first_name: None,
+            last_name: None,
+            is_friend: None,
+            avatar: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonEdge {
+    pub node: Person,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum PayRequestResponseStatus {
     Pending,
     Settled,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct PayRequestResponse {
     pub status: PayRequestResponseStatus,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct FundingInstrument {
     pub id: String,
     pub name: String,
     pub instrument_type: String,
+    #[serde(default)]
+    pub fees: Vec<InstrumentFee>,
+    #[serde(default)]
+    pub metadata: Option<InstrumentMetadata>,
+    #[serde(default)]
+    pub roles: Option<InstrumentRoles>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InstrumentFee {
+    #[serde(default)]
+    pub fee_type: Option<String>,
+    // in cents
+    #[serde(default)]
+    pub fixed_amount: Option<f64>,
+    #[serde(default)]
+    pub variable_percentage: Option<f64>,
+}
+
+// bank, card and balance metadata flattened together, since only one kind is ever filled in
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InstrumentMetadata {
+    #[serde(default)]
+    pub bank_name: Option<String>,
+    #[serde(default)]
+    pub is_verified: Option<bool>,
+    #[serde(default)]
+    pub issuer_name: Option<String>,
+    #[serde(default)]
+    pub network_name: Option<String>,
+    #[serde(default)]
+    pub last_four_digits: Option<String>,
+    #[serde(default)]
+    pub is_venmo_card: Option<bool>,
+    #[serde(default)]
+    pub expiration_date: Option<String>,
+    #[serde(default)]
+    pub expiration_status: Option<String>,
+    #[serde(default)]
+    pub available_balance: Option<AvailableBalance>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailableBalance {
+    #[serde(default)]
+    pub value: Option<f64>,
+    #[serde(default)]
+    pub display_string: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+// what the instrument may be used for, e.g. "default", "backup" or "not_eligible"
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InstrumentRoles {
+    #[serde(default)]
+    pub merchant_payments: Option<String>,
+    #[serde(default)]
+    pub peer_payments: Option<String>,
+}
+
+impl FundingInstrument {
+    // e.g. "Chase ••1234 (bank, verified)"
+    pub fn label(&self) -> String {
+        let meta = self.metadata.clone().unwrap_or_default();
+        let mut label = meta
+            .bank_name
+            .or(meta.issuer_name)
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| self.name.clone());
+        if let Some(last_four) = meta.last_four_digits.filter(|d| !d.is_empty()) {
+            label.push_str(&format!(" ••{last_four}"));
+        }
+
+        let mut details = vec![self.instrument_type.to_lowercase()];
+        match meta.is_verified {
+            Some(true) => details.push("verified".to_string()),
+            Some(false) => details.push("unverified".to_string()),
+            None => {}
+        }
+        if let Some(network) = meta.network_name.filter(|n| !n.is_empty()) {
+            details.push(network);
+        }
+        if meta
+            .expiration_status
+            .is_some_and(|s| s.eq_ignore_ascii_case("expired"))
+        {
+            details.push("expired".to_string());
+        }
+        if let Some(balance) = meta.available_balance.and_then(|b| b.display_string) {
+            details.push(balance);
+        }
+        format!("{label} ({})", details.join(", "))
+    }
+
+    // whether it can pay other people, as opposed to only merchants
+    pub fn peer_allowed(&self) -> bool {
+        !self
+            .roles
+            .as_ref()
+            .and_then(|r| r.peer_payments.as_deref())
+            .is_some_and(|r| {
+                r.eq_ignore_ascii_case("not_eligible") || r.eq_ignore_ascii_case("none")
+            })
+    }
+
+    pub fn is_balance(&self) -> bool {
+        self.instrument_type.eq_ignore_ascii_case("balance")
+    }
+
+    pub fn is_bank(&self) -> bool {
+        self.instrument_type.eq_ignore_ascii_case("bank")
+    }
+
+    // what's available in a venmo balance, from its display string, e.g. "$1,234.56"
+    pub fn balance_cents(&self) -> Option<i64> {
+        let balance = self.metadata.as_ref()?.available_balance.as_ref()?;
+        match &balance.display_string {
+            Some(display) => {
+                let digits = display
+                    .chars()
+                    .filter(|c| c.is_ascii_digit() || *c == '.')
+                    .collect::<String>();
+                Some((digits.parse::<f64>().ok()? * 100.0).round() as i64)
+            }
+            None => Some((balance.value? * 100.0).round() as i64),
+        }
+    }
+
+    pub fn has_fees(&self) -> bool {
+        self.fees.iter().any(|f| {
+            f.fixed_amount.is_some_and(|a| a > 0.0)
+                || f.variable_percentage.is_some_and(|p| p > 0.0)
+        })
+    }
+
+    // what paying `amount_cents` with this would cost on top, e.g. a credit card's 3%
+    pub fn fee_cents(&self, amount_cents: u32) -> i64 {
+        self.fees
+            .iter()
+            .map(|f| {
+                let fixed = f.fixed_amount.unwrap_or_default();
+                let variable =
+                    amount_cents as f64 * f.variable_percentage.unwrap_or_default() / 100.0;
+                (fixed + variable).round() as i64
+            })
+            .sum()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Eligibility {
     pub eligible: bool,
     pub eligibility_token: Option<String>,
+    #[serde(default)]
+    pub fees: Vec<Fee>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Fee {
+    #[serde(default)]
+    pub calculated_fee_amount_in_cents: i64,
+}
+
+impl Eligibility {
+    pub fn total_fees(&self) -> i64 {
+        self.fees
+            .iter()
+            .map(|f| f.calculated_fee_amount_in_cents)
+            .sum()
+    }
+}
+
+// signed dollars with two decimals, e.g. -1250 -> "-12.50"
+pub fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{sign}{}.{:02}", cents.abs() / 100, cents.abs() % 100)
+}
+
+// the same with a dollar sign after the sign, e.g. -1250 -> "-$12.50"
+pub fn format_dollars(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{sign}${}", format_cents(cents.abs()))
+}
+
+#[cfg(test)]
+impl Story {
+    // a p2p story between us and `other`, for tests. "- $x" amounts are ones we paid
+    pub fn fixture(id: &str, date: &str, amount: &str, other: &str, note: &str) -> Story {
+        let person = |username: &str| SenderReciever {
+            id: format!("{username}-id"),
+            display_name: username.to_uppercase(),
+            username: username.to_string(),
+        };
+        let (sender, receiver) = if amount.starts_with('-') {
+            (person("me"), person(other))
+        } else {
+            (person(other), person("me"))
+        };
+
+        Story {
+            amount: amount.to_string(),
+            avatar: String::new(),
+            initials: String::new(),
+            date: date.to_string(),
+            id: id.to_string(),
+            note: StoryNote {
+                content: Some(note.to_string()),
+            },
+            title: StoryTitle {
+                payload: StoryPayload {
+                    sub_type: StorySubType::P2p,
+                    action: Some("pay".to_string()),
+                },
+                content: None,
+                receiver: Some(receiver),
+                sender: Some(sender),
+            },
+            audience: None,
+            status: None,
+            funding_source: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_cents() {
+        assert_eq!(format_cents(1250), "12.50");
+        assert_eq!(format_cents(-5), "-0.05");
+        assert_eq!(format_cents(0), "0.00");
+        assert_eq!(format_dollars(1250), "$12.50");
+        assert_eq!(format_dollars(-1250), "-$12.50");
+    }
 }